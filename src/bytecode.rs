@@ -1,13 +1,19 @@
 //! The bytecode compiler for Dawn (dwn)
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{write, File};
 use std::io::{BufRead, BufReader, Read};
 use std::process::exit;
 
-use crate::dwn::{get_funcs, Metadata, Variable, CUSTOM_FUNCS, VARIABLES};
-use crate::lexer::{tokenize, Token, TokenModifiers, TokenTypes};
+use crate::dwn::{define_func, get_funcs, Metadata, CUSTOM_FUNCS, VARIABLES};
+use crate::lexer::{
+    check_unclosed, strip_comments, tokenize, LineState, Span, Token, TokenModifiers, TokenTypes,
+};
+use crate::module::{
+    bind_module, find_module, finish_loading, module_path, read_import, start_loading,
+};
 use crate::runner::run_tokens;
+use crate::value::{Function, Value};
 
 lazy_static! {
     static ref TYPES: HashMap<&'static str, TokenTypes> = {
@@ -24,6 +30,10 @@ lazy_static! {
         m.insert("v", TokenTypes::VARIABLE);
         m.insert("a", TokenTypes::ARRAY);
         m.insert("cf", TokenTypes::CUSTOMFUNC);
+        m.insert("t", TokenTypes::TEMPLATE);
+        m.insert("fv", TokenTypes::FUNCTION);
+        m.insert("m", TokenTypes::MAP);
+        m.insert("de", TokenTypes::DECIMAL);
         m
     };
 }
@@ -35,7 +45,7 @@ pub fn bytecode_compile_file(file: String, level: i32) {
     let level = if level < 0 { max_level } else { level };
 
     let reader =
-        BufReader::new(File::open(&file).expect(format!("Cannot open file `{}`", file).as_str()));
+        BufReader::new(File::open(&file).unwrap_or_else(|_| panic!("Cannot open file `{}`", file)));
 
     match level {
         1 => bytec_lvl1(reader, file),
@@ -48,64 +58,46 @@ pub fn bytecode_compile_file(file: String, level: i32) {
 
 fn bytec_lvl1(reader: BufReader<File>, file: String) {
     let mut scope = 0;
-    let mut in_scope = false;
+    let mut scope_depth = 0;
     let mut scope_token = String::new();
     let mut current_tokens = vec![];
+    let mut pending = String::new();
+    let mut line_state = LineState::default();
+    let mut scanned = HashSet::new();
+
+    let start = Span::start_of(&file);
 
     let mut bytecode = String::new();
     bytecode.push('1');
     bytecode.push('\x04');
+    bytecode.push_str(&file);
     bytecode.push('\n');
 
-    for (count, line) in reader.lines().enumerate() {
-        let line = remove_all_after(line.unwrap(), ';');
+    let mut last_line = 0;
 
-        if line.trim().is_empty() {
-            bytecode.push('\x03');
-            continue;
-        }
+    for (count, line) in reader.lines().enumerate() {
+        let line = strip_comments(&line.unwrap(), &mut line_state);
+        last_line = count + 1;
 
         let tokens = tokenize(
-            line.trim_end().to_string(),
+            line,
             &mut Metadata {
-                line_count: count,
+                span: Span {
+                    line: count + 1,
+                    ..start.clone()
+                },
                 scope: &mut scope,
-                in_scope: &mut in_scope,
+                scope_depth: &mut scope_depth,
                 scope_token: &mut scope_token,
                 current_tokens: &mut current_tokens,
+                pending: &mut pending,
+                flow: &mut None,
+                loop_depth: &mut 0,
+                call_depth: &mut 0,
             },
         );
 
-        if (tokens.len() > 0
-            && tokens[0]
-                == Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "create_var".to_string(),
-                })
-        {
-            VARIABLES.write().unwrap().insert(
-                tokens[1].val.to_string(),
-                Variable {
-                    scope,
-                    value: tokens[2].clone(),
-                },
-            );
-        }
-
-        if (tokens.len() > 0
-            && tokens[0]
-                == Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "func".to_string(),
-                })
-        {
-            CUSTOM_FUNCS
-                .write()
-                .unwrap()
-                .insert(tokens[1].val.to_string(), tokens[2].clone());
-        }
+        declare_names(&tokens, &mut scanned);
 
         for token in tokens {
             let mut type_ = "na";
@@ -136,13 +128,19 @@ fn bytec_lvl1(reader: BufReader<File>, file: String) {
             }
 
             bytecode.push('\x00');
-            bytecode.push_str(&value);
+            bytecode.push_str(&token.span.line.to_string());
+            bytecode.push('\x01');
+            bytecode.push_str(&token.span.column.to_string());
+            bytecode.push('\x00');
+            bytecode.push_str(&encode_value(&value));
             bytecode.push('\x02');
         }
 
         bytecode.push('\x03');
     }
 
+    check_unclosed(&pending, scope_depth, &scope_token, &start, last_line);
+
     let mut file_without_ext = file.split('.').collect::<Vec<&str>>();
     file_without_ext.pop();
 
@@ -158,9 +156,112 @@ fn bytec_lvl1(reader: BufReader<File>, file: String) {
     }
 }
 
+/// Makes the names that `tokens` define known to the lexer without running them, so the lines
+/// after them are compiled the way they will run. An `import` scans its module the same way.
+fn declare_names(tokens: &[Token], scanned: &mut HashSet<String>) {
+    if tokens.is_empty() || tokens[0].ty != TokenTypes::FUNC {
+        return;
+    }
+
+    match tokens[0].val.as_str() {
+        "create_var" => {
+            // Only the name matters to the lexer, and whether it holds a function. A `let` without
+            // them is reported when it runs.
+            let value = match tokens.get(2) {
+                Some(token) if token.ty == TokenTypes::FUNCTION => {
+                    Value::Function(Function::Named(token.val.to_string()))
+                }
+                _ => Value::None,
+            };
+
+            if let Some(name) = tokens.get(1) {
+                VARIABLES
+                    .write()
+                    .unwrap()
+                    .declare(name.val.to_string(), value);
+            }
+        }
+        "func" => {
+            if let Ok((name, mut custom_func)) = define_func(&tokens[1..]) {
+                let variables = VARIABLES.read().unwrap();
+                let key = variables.function_key(&name);
+                custom_func.module = variables.module().to_string();
+                drop(variables);
+
+                CUSTOM_FUNCS.write().unwrap().insert(key, custom_func);
+            }
+        }
+        "import" => {
+            // Only a module named by a string can be found before running.
+            if let Ok((path, import)) = read_import(&tokens[1..]) {
+                if path.ty == TokenTypes::STRING {
+                    if let Ok(module) = find_module(&path.val, &path.span.file) {
+                        scan_module(&module, scanned);
+                        let _ = bind_module(&module, import);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Declares the names of the top level of the module at `path`, once, like [`bytec_lvl1`] does
+/// for the file it compiles.
+fn scan_module(path: &str, scanned: &mut HashSet<String>) {
+    if !scanned.insert(path.to_string()) {
+        return;
+    }
+
+    let reader = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(_) => return,
+    };
+
+    let mut scope = 0;
+    let mut scope_depth = 0;
+    let mut scope_token = String::new();
+    let mut current_tokens = vec![];
+    let mut pending = String::new();
+    let mut line_state = LineState::default();
+    let start = Span::start_of(path);
+
+    let outer = VARIABLES.write().unwrap().enter(path.to_string());
+
+    for (count, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => strip_comments(&line, &mut line_state),
+            Err(_) => break,
+        };
+
+        let tokens = tokenize(
+            line,
+            &mut Metadata {
+                span: Span {
+                    line: count + 1,
+                    ..start.clone()
+                },
+                scope: &mut scope,
+                scope_depth: &mut scope_depth,
+                scope_token: &mut scope_token,
+                current_tokens: &mut current_tokens,
+                pending: &mut pending,
+                flow: &mut None,
+                loop_depth: &mut 0,
+                call_depth: &mut 0,
+            },
+        );
+
+        declare_names(&tokens, scanned);
+    }
+
+    VARIABLES.write().unwrap().leave(outer);
+}
+
 pub fn bytecode_run(bytecode_file: String) {
     let mut reader = BufReader::new(
-        File::open(&bytecode_file).expect(format!("Cannot open file `{}`", bytecode_file).as_str()),
+        File::open(&bytecode_file)
+            .unwrap_or_else(|_| panic!("Cannot open file `{}`", bytecode_file)),
     );
 
     let mut level = String::new();
@@ -172,7 +273,9 @@ pub fn bytecode_run(bytecode_file: String) {
         }
     }
 
-    let level = match level.split('\x04').next() {
+    let mut header = level.trim_end_matches('\n').split('\x04');
+
+    let level = match header.next() {
         Some(l) => match l.parse::<i64>() {
             Ok(l) => l,
             Err(_) => {
@@ -186,8 +289,14 @@ pub fn bytecode_run(bytecode_file: String) {
         }
     };
 
+    // Older bytecode files do not record the file they were compiled from.
+    let file = match header.next() {
+        Some(file) if !file.is_empty() => file.to_string(),
+        _ => bytecode_file,
+    };
+
     match level {
-        1 => byterun_lvl1(reader),
+        1 => byterun_lvl1(reader, file),
         lvl => {
             eprintln!("Error: Bytecode runner level {lvl} has not been implemented!");
             exit(1);
@@ -195,7 +304,7 @@ pub fn bytecode_run(bytecode_file: String) {
     }
 }
 
-fn byterun_lvl1(mut reader: BufReader<File>) {
+fn byterun_lvl1(mut reader: BufReader<File>, file: String) {
     let mut text = String::new();
 
     match reader.read_to_string(&mut text) {
@@ -208,22 +317,34 @@ fn byterun_lvl1(mut reader: BufReader<File>) {
 
     let bytecode_lines: Vec<&str> = text.split('\x03').collect();
     let mut scope = 0;
-    let mut in_scope = false;
+    let mut scope_depth = 0;
     let mut scope_token = String::new();
     let mut current_tokens = vec![];
+    let mut pending = String::new();
+    let start = Span::start_of(&file);
+
+    // The main file cannot be imported while it runs, like in `dwn run`.
+    let path = module_path(&file);
+    start_loading(&path).ok();
 
     for (count, bytecode_line) in bytecode_lines.iter().enumerate() {
         let tokens: Vec<&str> = bytecode_line.split('\x02').collect();
         let mut tokens_vec: Vec<Token> = vec![];
 
         for token in &tokens[..tokens.len() - 1] {
-            let token_parts: Vec<&str> = token.split('\x00').collect();
+            let token_parts: Vec<&str> = token.splitn(4, '\x00').collect();
 
             let mut token_parts = token_parts.iter();
 
             let type_part = token_parts.next();
             let modifier_part = token_parts.next();
-            let value_part = token_parts.next();
+
+            // Older bytecode files do not have a span part, so fall back to the line's number.
+            let (span_part, value_part) = if token_parts.len() == 2 {
+                (token_parts.next(), token_parts.next())
+            } else {
+                (None, token_parts.next())
+            };
 
             let type_ = match type_part {
                 Some(ty) => {
@@ -267,51 +388,155 @@ fn byterun_lvl1(mut reader: BufReader<File>) {
             };
 
             let val = match value_part {
-                Some(val) => val.to_string(),
+                Some(val) => decode_value(val),
                 None => {
                     eprintln!("(val_get no_part_found) Error: Invalid format in bytecode file!");
                     exit(1);
                 }
             };
 
+            let span = match span_part {
+                Some(span_part) => {
+                    let position: Vec<usize> = span_part
+                        .split('\x01')
+                        .map(|part| match part.parse() {
+                            Ok(n) => n,
+                            Err(_) => {
+                                eprintln!("(span_get not_numeric: >>{part}<<) Error: Invalid format in bytecode file!");
+                                exit(1);
+                            }
+                        })
+                        .collect();
+
+                    match position[..] {
+                        [line, column] => Span {
+                            line,
+                            column,
+                            ..start.clone()
+                        },
+                        _ => {
+                            eprintln!("(span_get no_found: >>{span_part}<<) Error: Invalid format in bytecode file!");
+                            exit(1);
+                        }
+                    }
+                }
+                None => Span {
+                    line: count + 1,
+                    ..start.clone()
+                },
+            };
+
             tokens_vec.push(Token {
                 ty: type_.clone(),
                 modifiers,
                 val,
+                span,
             });
         }
 
+        let span = match tokens_vec.first() {
+            Some(token) => token.span.clone(),
+            None => Span {
+                line: count + 1,
+                ..start.clone()
+            },
+        };
+
         run_tokens(
             tokens_vec,
             get_funcs(),
             &mut Metadata {
-                line_count: count,
+                span,
                 scope: &mut scope,
-                in_scope: &mut in_scope,
+                scope_depth: &mut scope_depth,
                 scope_token: &mut scope_token,
                 current_tokens: &mut current_tokens,
+                pending: &mut pending,
+                flow: &mut None,
+                loop_depth: &mut 0,
+                call_depth: &mut 0,
             },
             false,
         )
         .ok();
     }
+
+    finish_loading(&path, true);
 }
 
-/// The function to remove every character in `text` after `ch` is reached (including `ch`).
+/// Replaces the characters that the bytecode format uses as separators (`\x00` to `\x06`) so that
+/// values such as strings with escape sequences can be stored unchanged.
 ///
 /// Examples:
 ///
 /// ```rust
-/// let new = remove_all_after("say \"Hello!\" ; abcdefghij...".to_string(), ';');
+/// let encoded = encode_value("a\x03b");
 ///
-/// assert_eq!(new, "say \"Hello!\" ".to_string());
+/// assert_eq!(encoded, "a\x063b".to_string());
+/// assert_eq!(decode_value(&encoded), "a\x03b".to_string());
 /// ```
-fn remove_all_after(text: String, ch: char) -> String {
-    text.split(ch).next().unwrap().to_string()
+fn encode_value(value: &str) -> String {
+    let mut encoded = String::new();
+
+    for c in value.chars() {
+        if c <= '\x06' {
+            encoded.push('\x06');
+            encoded.push((b'0' + c as u8) as char);
+        } else {
+            encoded.push(c);
+        }
+    }
+
+    encoded
+}
+
+/// Reverses [`encode_value`].
+fn decode_value(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x06' {
+            if let Some(code) = chars.next() {
+                decoded.push(((code as u8).wrapping_sub(b'0')) as char);
+            }
+        } else {
+            decoded.push(c);
+        }
+    }
+
+    decoded
+}
+
+#[test]
+fn encoding_values() {
+    let value = "a\x00b\x03\nc\x06";
+    let encoded = encode_value(value);
+
+    assert!(!encoded.contains(['\x00', '\x01', '\x02', '\x03', '\x04', '\x05']));
+    assert_eq!(decode_value(&encoded), value.to_string());
 }
 
 #[test]
-fn removing_all_after() {
-    let new = remove_all_after("say \"Hello!\" ; abcdefghij...".to_string(), ';');
-    assert_eq!(new, "say \"Hello!\" ".to_string());
+fn declaring_incomplete_lets() {
+    let token = |ty, val: &str| Token {
+        ty,
+        modifiers: vec![TokenModifiers::ARGS],
+        val: val.to_string(),
+        span: Span::start_of("test.dwn"),
+    };
+
+    declare_names(
+        &[token(TokenTypes::FUNC, "create_var")],
+        &mut HashSet::new(),
+    );
+    declare_names(
+        &[
+            token(TokenTypes::FUNC, "create_var"),
+            token(TokenTypes::STRING, "declared_without_value"),
+        ],
+        &mut HashSet::new(),
+    );
+
+    assert!(VARIABLES.read().unwrap().contains("declared_without_value"));
 }
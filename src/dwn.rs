@@ -1,29 +1,66 @@
 //! This is the main file for the definitions for Dawn's (dwn's) functions and variables
 
 use std::{
+    cmp::Ordering,
     collections::HashMap,
     io::{stdin, stdout, Write},
+    iter,
+    mem::replace,
     process::exit,
-    sync::{RwLock, RwLockReadGuard},
+    sync::{Arc, RwLock, RwLockReadGuard},
 };
 
+use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, ToPrimitive, Zero};
+use num_bigint::{BigInt, Sign};
+
 use crate::{
-    lexer::{tokenize, Token, TokenModifiers, TokenTypes},
-    runner::run,
+    env::{Call, Environment, Scope},
+    error::{Error, ErrorKind},
+    lexer::{template_parts, tokenize, Span, TemplatePart, Token, TokenModifiers, TokenTypes},
+    module::{bind_module, find_module, load_module, read_import},
+    runner::run_tokens,
+    value::{Function, Value},
 };
 
-#[derive(Clone)]
-pub struct Variable {
-    pub value: Token,
-    pub scope: u32,
+/// A function defined with `func` or made with `fn`.
+#[derive(Clone, Debug)]
+pub struct CustomFunc {
+    pub params: Vec<String>,
+    pub body: Token,
+    /// The scopes it was made in, which it sees on every call.
+    pub captured: Vec<Scope>,
+    /// The module it was defined in, whose global variables it sees.
+    pub module: String,
+}
+
+/// The signature of every built-in function in [`FUNCTIONS`].
+pub type Builtin = for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Value, Error>;
+
+/// How a scope run by [`run_scope`] ended.
+pub enum Flow {
+    /// It ran to its end, giving the value of its last line.
+    Normal(Value),
+    /// It was ended by `break`.
+    Break,
+    /// It was ended by `continue`.
+    Continue,
+    /// It was ended by `return`, giving its value.
+    Return(Value),
 }
 
 pub struct Metadata<'a> {
-    pub line_count: usize,
+    pub span: Span,
     pub scope: &'a mut u32,
-    pub in_scope: &'a mut bool,
+    pub scope_depth: &'a mut usize,
     pub scope_token: &'a mut String,
     pub current_tokens: &'a mut Vec<Token>,
+    pub pending: &'a mut String,
+    /// Set by `break`, `continue` and `return` until [`run_scope`] ends the scope they are in.
+    pub flow: &'a mut Option<Flow>,
+    /// How many loops are around the line being run, inside the current function call.
+    pub loop_depth: &'a mut usize,
+    /// How many function calls are running around the line being run.
+    pub call_depth: &'a mut usize,
 }
 
 lazy_static! {
@@ -36,142 +73,96 @@ lazy_static! {
     ///
     /// assert!(functions.contains_key(&"say"))
     /// ```
-    pub static ref FUNCTIONS: RwLock<HashMap<&'static str, for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>>> = {
+    pub static ref FUNCTIONS: RwLock<HashMap<&'static str, Builtin>> = {
         let mut m = HashMap::new();
-        m.insert("say", say as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>);
-        m.insert(
-            "short_say",
-            short_say as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert("ask", ask as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>);
-        m.insert(
-            "create_var",
-            create_var as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert("sum", sum as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>);
-        m.insert(
-            "difference",
-            difference as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "product",
-            product as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "quotient",
-            quotient as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "forever",
-            forever as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "scope",
-            scope as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "if",
-            if_ as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "while",
-            while_ as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "until",
-            until as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "eq",
-            eq as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "ne",
-            ne as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "gt",
-            gt as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "lt",
-            lt as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "add_assign",
-            add_assign as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "subtract_assign",
-            subtract_assign as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "multiply_assign",
-            multiply_assign as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "divide_assign",
-            divide_assign as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "break",
-            break_ as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "lazy_eq",
-            lazy_eq as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "lazy_ne",
-            lazy_ne as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "int",
-            int as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "float",
-            float as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "vars",
-            vars as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "format_array",
-            format_array as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "quit",
-            quit as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "exit",
-            quit as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
-        m.insert(
-            "func",
-            func as for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        );
+        m.insert("say", say as Builtin);
+        m.insert("short_say", short_say as Builtin);
+        m.insert("ask", ask as Builtin);
+        m.insert("create_var", create_var as Builtin);
+        m.insert("assign", assign as Builtin);
+        m.insert("sum", sum as Builtin);
+        m.insert("difference", difference as Builtin);
+        m.insert("product", product as Builtin);
+        m.insert("quotient", quotient as Builtin);
+        m.insert("int_quotient", int_quotient as Builtin);
+        m.insert("remainder", remainder as Builtin);
+        m.insert("forever", forever as Builtin);
+        m.insert("scope", scope as Builtin);
+        m.insert("if", if_ as Builtin);
+        m.insert("while", while_ as Builtin);
+        m.insert("until", until as Builtin);
+        m.insert("for", for_ as Builtin);
+        m.insert("eq", eq as Builtin);
+        m.insert("ne", ne as Builtin);
+        m.insert("gt", gt as Builtin);
+        m.insert("lt", lt as Builtin);
+        m.insert("ge", ge as Builtin);
+        m.insert("le", le as Builtin);
+        m.insert("logical_and", logical_and as Builtin);
+        m.insert("logical_or", logical_or as Builtin);
+        m.insert("logical_not", logical_not as Builtin);
+        m.insert("add_assign", add_assign as Builtin);
+        m.insert("subtract_assign", subtract_assign as Builtin);
+        m.insert("multiply_assign", multiply_assign as Builtin);
+        m.insert("divide_assign", divide_assign as Builtin);
+        m.insert("break", break_ as Builtin);
+        m.insert("continue", continue_ as Builtin);
+        m.insert("return", return_ as Builtin);
+        m.insert("try", try_ as Builtin);
+        m.insert("raise", raise as Builtin);
+        m.insert("throw", raise as Builtin);
+        m.insert("lazy_eq", lazy_eq as Builtin);
+        m.insert("lazy_ne", lazy_ne as Builtin);
+        m.insert("int", int as Builtin);
+        m.insert("float", float as Builtin);
+        m.insert("decimal", decimal as Builtin);
+        m.insert("vars", vars as Builtin);
+        m.insert("format_array", format_array as Builtin);
+        m.insert("quit", quit as Builtin);
+        m.insert("exit", quit as Builtin);
+        m.insert("func", func as Builtin);
+        m.insert("fn", fn_ as Builtin);
+        m.insert("import", import as Builtin);
+        m.insert("get", get as Builtin);
+        m.insert("set", set as Builtin);
+        m.insert("remove", remove as Builtin);
+        m.insert("keys", keys as Builtin);
+        m.insert("values", values as Builtin);
+        m.insert("has", has as Builtin);
+        m.insert("len", len as Builtin);
+        m.insert("slice", slice as Builtin);
+        m.insert("push", push as Builtin);
+        m.insert("pop", pop as Builtin);
+        m.insert("insert", insert as Builtin);
+        m.insert("contains", contains as Builtin);
+        m.insert("reverse", reverse as Builtin);
+        m.insert("sort", sort as Builtin);
+        m.insert("upper", upper as Builtin);
+        m.insert("lower", lower as Builtin);
+        m.insert("trim", trim as Builtin);
+        m.insert("split", split as Builtin);
+        m.insert("join", join as Builtin);
+        m.insert("replace", replace_ as Builtin);
+        m.insert("starts_with", starts_with as Builtin);
+        m.insert("ends_with", ends_with as Builtin);
+        m.insert("find", find as Builtin);
+        m.insert("repeat", repeat as Builtin);
+        m.insert("chars", chars as Builtin);
         RwLock::new(m)
     };
 }
 lazy_static! {
-    /// The variables HashMap
+    /// The variables, in a frame for every scope that is running
     ///
     /// Examples:
     ///
     /// ```rust
     /// let variables = VARIABLES.read().unwrap();
-    /// assert!(variables.contains_key(&"$hello"))
+    /// assert!(variables.contains(&"$hello"))
     /// ```
-    pub static ref VARIABLES: RwLock<HashMap<String, Variable>> = {
-        let mut m = HashMap::new();
-        m.insert(String::from("$hello"), Variable { value: Token {
-            ty: TokenTypes::STRING,
-            modifiers: vec![],
-            val: "Hello, World!".to_string(),
-        }, scope: 0 });
+    pub static ref VARIABLES: RwLock<Environment> = {
+        let mut m = Environment::new();
+        m.declare(String::from("$hello"), Value::Str("Hello, World!".to_string()));
 
         RwLock::new(m)
     };
@@ -186,7 +177,7 @@ lazy_static! {
     /// let variables = CUSTOM_FUNCS.read().unwrap();
     /// assert!(variables.contains_key(&"some"))
     /// ```
-    pub static ref CUSTOM_FUNCS: RwLock<HashMap<String, Token>> = {
+    pub static ref CUSTOM_FUNCS: RwLock<HashMap<String, CustomFunc>> = {
         let m = HashMap::new();
 
         RwLock::new(m)
@@ -200,158 +191,288 @@ lazy_static! {
 /// ```rust
 /// let tokens = vec![
 ///     Token {
-///           ty: TokenTypes::VARIABLE,
+///           ty: TokenTypes::INT,
 ///           modifiers: vec![TokenModifiers::ARGS],
-///           val: "a"
+///           val: "1"
 ///     },
 ///     Token {
-///           ty: TokenTypes::VARIABLE,
+///           ty: TokenTypes::INT,
 ///           modifiers: vec![],
-///           val: "b"  
+///           val: "2"
 ///     }
 /// ];
-/// let args = get_args(tokens, meta, false);
+/// let args = get_args(tokens, meta, false)?;
 ///
-/// assert_eq!(
-///     args,
-///     vec![
-///         Token {
-///             ty: TokenTypes::VARIABLE,
-///             modifiers: vec![TokenModifiers::ARGS],
-///             val: "a"
-///         }
-///     ]
-/// );
+/// assert_eq!(args, vec![Value::Int(1)]);
 /// ```
-fn get_args(tokens: Vec<Token>, meta: &mut Metadata, tolerate_names: bool) -> Vec<Token> {
-    let mut args: Vec<Token> = vec![];
-
-    for token in tokens {
+fn get_args(
+    tokens: Vec<Token>,
+    meta: &mut Metadata,
+    tolerate_names: bool,
+) -> Result<Vec<Value>, Error> {
+    let mut args: Vec<Value> = vec![];
+
+    for (i, token) in tokens.iter().enumerate() {
         if !token.modifiers.contains(&TokenModifiers::ARGS) {
             break;
         }
 
-        let token = match token.ty {
-            TokenTypes::LITERAL => {
-                let ret = run(token.val, get_funcs(), meta);
-                ret
-            }
-            TokenTypes::VARIABLE => {
-                let variables = get_variables();
-                let variable = variables.get(&token.val);
-                let variable = match variable {
-                    Some(var) => var,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: Variable '{}' does not exist!",
-                            meta.line_count + 1,
-                            token.val
-                        );
-                        exit(1);
+        // `fn params { ... }` takes the rest of the arguments as its parameters and body, and
+        // `if c { ... } else { ... }` as its branches.
+        if token.ty == TokenTypes::FUNC && (token.val == "fn" || token.val == "if") {
+            let f = if token.val == "fn" { fn_ } else { if_ };
+            args.push(f(tokens[i + 1..].to_vec(), meta).map_err(|err| err.at(&token.span))?);
+            break;
+        }
+
+        args.push(get_arg(token.clone(), meta, tolerate_names)?);
+    }
+    Ok(args)
+}
+
+/// Gets the value of a single argument: literals are run, variables are looked up and
+/// interpolated strings are filled in. Fails with the error of the code it runs.
+///
+/// Examples:
+///
+/// ```rust
+/// let arg = get_arg(
+///     Token {
+///         ty: TokenTypes::LITERAL,
+///         modifiers: vec![TokenModifiers::ARGS],
+///         val: "1 + 2".to_string(),
+///         span: Span::default(),
+///     },
+///     meta,
+///     false,
+/// )?;
+///
+/// assert_eq!(arg, Value::Int(3));
+/// ```
+pub fn get_arg(token: Token, meta: &mut Metadata, tolerate_names: bool) -> Result<Value, Error> {
+    let value = match token.ty {
+        TokenTypes::LITERAL => run_at(token.val, &token.span, meta)?,
+        TokenTypes::TEMPLATE => {
+            let mut string = String::new();
+
+            for part in template_parts(&token) {
+                match part {
+                    TemplatePart::Text(text) => string.push_str(&text),
+                    TemplatePart::Placeholder(code, span) => {
+                        string.push_str(&run_at(code, &span, meta)?.to_string())
                     }
-                };
-                let val = &variable.value;
-                Token {
-                    ty: val.ty.clone(),
-                    modifiers: val.modifiers.clone(),
-                    val: val.val.to_string(),
                 }
             }
-            TokenTypes::NAME => {
-                if !tolerate_names {
-                    eprintln!(
-                        "Error on line {}: Name '{}' does not exist!",
-                        meta.line_count + 1,
-                        token.val
-                    );
-                    exit(1);
+
+            Value::Str(string)
+        }
+        TokenTypes::VARIABLE => match get_variables().get(&token.val) {
+            Some(variable) => variable,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::Name,
+                    format!("Variable '{}' does not exist!", token.val),
+                )
+                .at(&token.span))
+            }
+        },
+        // Array and map literals are run once, when they are made.
+        TokenTypes::ARRAY => Value::Array(read_array(&token, meta)?),
+        TokenTypes::MAP => {
+            let mut entries: Vec<(Value, Value)> = vec![];
+
+            for (key, value) in read_map(&token, meta)? {
+                check_key(&key, "map").map_err(|err| err.at(&token.span))?;
+
+                match find_key(&entries, &key) {
+                    Some(i) => entries[i].1 = value,
+                    None => entries.push((key, value)),
                 }
-                token
             }
-            _ => token,
-        };
 
-        args.push(token);
-    }
-    args
+            Value::Map(entries)
+        }
+        TokenTypes::STRING => Value::Str(token.val),
+        TokenTypes::INT => match token.val.parse() {
+            Ok(int) => Value::Int(int),
+            Err(_) => Value::integer(token.val.parse().unwrap()),
+        },
+        TokenTypes::FLOAT => Value::Float(token.val.parse().unwrap()),
+        TokenTypes::DECIMAL => Value::Decimal(token.val.parse().unwrap()),
+        TokenTypes::BOOL => Value::Bool(token.val == "true"),
+        TokenTypes::NONE => Value::None,
+        // A function name read on its own, like an array item, is the function as a value.
+        TokenTypes::FUNCTION => Value::Function(Function::Named(token.val)),
+        TokenTypes::CUSTOMFUNC => match get_variables().with(&token.val, Value::function) {
+            Some(Some(function)) => Value::Function(function),
+            _ => Value::Function(Function::Named(token.val)),
+        },
+        TokenTypes::NAME => {
+            if !tolerate_names {
+                return Err(Error::new(
+                    ErrorKind::Name,
+                    format!("Name '{}' does not exist!", token.val),
+                )
+                .at(&token.span));
+            }
+            Value::Str(token.val)
+        }
+        TokenTypes::FUNC | TokenTypes::SCOPE => {
+            return Err(Error::new(ErrorKind::Syntax, "Expected a value!").at(&token.span))
+        }
+    };
+
+    Ok(value)
 }
 
-/// Runs a scope in Dawn (dwn)
+/// Runs a scope in Dawn (dwn). An error stops the scope, which still drops its variables.
 ///
 /// Examples:
 ///
 /// ```rust
-/// let stat: Option<String> = run_scope(token, meta);
+/// let flow: Flow = run_scope(token, meta)?;
 ///
-/// match stat {
-///     Some(stat) => println!("Breaking scope!"),
-///     None => println!("It's fine..."),
+/// match flow {
+///     Flow::Break => println!("Breaking scope!"),
+///     _ => println!("It's fine..."),
 /// }
 /// ```
-pub fn run_scope(token: &Token, meta: &mut Metadata) -> Token {
+pub fn run_scope(token: &Token, meta: &mut Metadata) -> Result<Flow, Error> {
     match token.ty {
         TokenTypes::SCOPE => {
             *meta.scope += 1;
-            let mut ret = Token {
-                ty: TokenTypes::NONE,
-                modifiers: vec![],
-                val: "None".to_string(),
-            };
+            VARIABLES.write().unwrap().push(vec![], None);
+            let mut ret = Ok(Value::None);
 
-            for line in token.val.lines() {
-                ret = run(line.to_string(), get_funcs(), meta);
-
-                if (ret
-                    == Token {
-                        ty: TokenTypes::FUNC,
-                        modifiers: vec![],
-                        val: "break".to_string(),
-                    })
-                {
-                    return ret;
+            for (count, line) in token.val.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
                 }
-            }
 
-            let mut drop_vars: Vec<String> = vec![];
-            let mut variables = VARIABLES.write().unwrap();
+                let span = Span {
+                    line: token.span.line + count,
+                    ..token.span.clone()
+                };
+
+                ret = run_at(line.to_string(), &span, meta);
 
-            for (k, v) in variables.clone() {
-                if v.scope == *meta.scope {
-                    drop_vars.push(k);
+                if ret.is_err() || meta.flow.is_some() {
+                    break;
                 }
             }
 
-            for k in drop_vars {
-                variables.remove(&k);
+            VARIABLES.write().unwrap().pop();
+            *meta.scope -= 1;
+
+            let ret = ret?;
+
+            match meta.flow.take() {
+                Some(flow) => Ok(flow),
+                None => Ok(Flow::Normal(ret)),
             }
+        }
+        _ => Err(Error::new(ErrorKind::Syntax, "Expected scope!").at(&token.span)),
+    }
+}
 
-            *meta.scope -= 1;
+/// Reads the items of an array literal. Each item is read from where it is written, so that
+/// errors inside it point at its own line.
+fn read_array(token: &Token, meta: &mut Metadata) -> Result<Vec<Value>, Error> {
+    let mut items: Vec<Value> = vec![];
+    let mut span = token.span.after("[");
 
-            return ret;
+    for item in token.val.split('\x05') {
+        if !item.trim().is_empty() {
+            items.push(read_item(item, &span, meta)?);
         }
-        _ => {
-            eprintln!("Error on line {}: Expected scope!", meta.line_count + 1);
-            exit(1);
+
+        span = span.after(item).after(",");
+    }
+
+    Ok(items)
+}
+
+/// Reads the keys and values of a map literal, in the order they were written.
+fn read_map(token: &Token, meta: &mut Metadata) -> Result<Vec<(Value, Value)>, Error> {
+    let mut entries: Vec<(Value, Value)> = vec![];
+    let mut span = token.span.after("#{");
+
+    for entry in token.val.split('\x05') {
+        if let Some((key, value)) = entry.split_once('\x06') {
+            let value_span = span.after(key).after(":");
+
+            entries.push((
+                read_item(key, &span, meta)?,
+                read_item(value, &value_span, meta)?,
+            ));
         }
+
+        span = span.after(entry).after(",");
     }
+
+    Ok(entries)
 }
 
-fn read_array(token: &Token, meta: &mut Metadata) -> Vec<Token> {
-    let mut array: Vec<Token> = vec![];
+/// Gives the value of the code of an array or map item, written at `span`. The name of a
+/// function gives the function instead of calling it.
+fn read_item(source: &str, span: &Span, meta: &mut Metadata) -> Result<Value, Error> {
+    // The item is read from its first character, past the whitespace written before it.
+    let code = source.trim_start();
+    let span = span.after(&source[..source.len() - code.len()]);
 
-    let array_items: Vec<&str> = token.val.split('\x05').collect();
+    let outer = replace(&mut meta.span, span);
+    let tokens = tokenize(code.trim_end().to_string(), meta);
 
-    for array_item in array_items {
-        let tokens = tokenize(array_item.to_string(), meta);
+    let item = if tokens.len() == 1 {
+        get_arg(tokens[0].clone(), meta, false)
+    } else {
+        run_tokens(tokens, get_funcs(), meta, true)
+    };
 
-        for token in tokens {
-            if !token.val.is_empty() {
-                array.push(token);
-            }
+    meta.span = outer;
+
+    item
+}
+
+/// Runs `line` as if it started at `span`, so that errors inside it point at the right place.
+/// Errors are given back instead of stopping the program, so that a `try` around can catch them.
+///
+/// Examples:
+///
+/// ```rust
+/// let ret = run_at("sum 1 2".to_string(), &token.span, meta)?;
+///
+/// assert_eq!(ret, Value::Int(3));
+/// ```
+pub fn run_at(line: String, span: &Span, meta: &mut Metadata) -> Result<Value, Error> {
+    let outer = replace(&mut meta.span, span.clone());
+    let tokens = tokenize(line, meta);
+    let ret = run_tokens(tokens, get_funcs(), meta, true);
+    meta.span = outer;
+
+    ret
+}
+
+/// Gives the value of a scope that is not a loop body, passing a `break`, `continue` or `return`
+/// that ended it on to the scope around it.
+fn pass_on(flow: Flow, meta: &mut Metadata) -> Value {
+    match flow {
+        Flow::Normal(ret) => ret,
+        flow => {
+            *meta.flow = Some(flow);
+
+            Value::None
         }
     }
+}
+
+/// Runs one pass of a loop body, so that `break` and `continue` inside it are allowed.
+fn run_loop_body(token: &Token, meta: &mut Metadata) -> Result<Flow, Error> {
+    *meta.loop_depth += 1;
+    let flow = run_scope(token, meta);
+    *meta.loop_depth -= 1;
 
-    array
+    flow
 }
 
 /// Gets the functions HashMap
@@ -363,10 +484,7 @@ fn read_array(token: &Token, meta: &mut Metadata) -> Vec<Token> {
 ///
 /// assert!(functions.contains_key(&"say"))
 /// ```
-pub fn get_funcs() -> RwLockReadGuard<
-    'static,
-    HashMap<&'static str, for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>>,
-> {
+pub fn get_funcs() -> RwLockReadGuard<'static, HashMap<&'static str, Builtin>> {
     FUNCTIONS
         .read()
         .expect("Error: Another user of this mutex panicked while holding the mutex!")
@@ -379,53 +497,48 @@ pub fn get_funcs() -> RwLockReadGuard<
 /// ```rust
 /// let variables = get_variables();
 ///
-/// assert!(variables.contains_key(&"$hello"))
+/// assert!(variables.contains(&"$hello"))
 /// ```
-pub fn get_variables() -> RwLockReadGuard<'static, HashMap<String, Variable>> {
+pub fn get_variables() -> RwLockReadGuard<'static, Environment> {
     VARIABLES
         .read()
         .expect("Error: Another user of this mutex panicked while holding the mutex!")
 }
 
-fn say(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+fn say(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
     for arg in args {
-        print!("{} ", arg.val);
+        print!("{arg} ");
     }
 
     println!();
 
-    Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
-    })
+    Ok(Value::None)
 }
 
-fn short_say(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+fn short_say(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
     for arg in args {
-        print!("{} ", arg.val);
+        print!("{arg} ");
     }
 
-    Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
-    })
+    Ok(Value::None)
 }
 
-fn ask(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+fn ask(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-    if args.len() < 1 {
-        return Err("(ask) Not enough arguments!".to_string());
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(ask) Not enough arguments!",
+        ));
     }
 
     let mut input = String::new();
-    let prompt = &args[0].val;
+    let prompt = &args[0];
 
     print!("{}", prompt);
 
@@ -434,7 +547,7 @@ fn ask(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
         Err(e) => {
             let e = e.to_string();
 
-            return Err(format!("(ask) {e}"));
+            return Err(Error::new(ErrorKind::Io, format!("(ask) {e}")));
         }
     }
 
@@ -443,949 +556,2147 @@ fn ask(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
         Err(e) => {
             let e = e.to_string();
 
-            return Err(format!("(ask) {e}"));
+            return Err(Error::new(ErrorKind::Io, format!("(ask) {e}")));
         }
     }
 
-    Ok(Token {
-        ty: TokenTypes::STRING,
-        modifiers: vec![],
-        val: input.trim().to_string(),
-    })
+    Ok(Value::Str(input.trim().to_string()))
 }
 
-fn create_var(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
-    let var_name = args[0].val.to_string();
-    let var_value = args[1].val.to_string();
-
-    if args.len() < 1 {
-        return Err("(let) Not enough arguments!".to_string());
-    }
+fn create_var(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let mut args = get_args(tokens, meta, false)?.into_iter();
 
-    match args[0].ty {
-        TokenTypes::NONE => return Err(format!("(let) Cannot accept none as variable name!")),
-        _ => {}
-    }
+    let (var_name, var_value) = match (args.next(), args.next()) {
+        (Some(Value::Str(name)), Some(value)) => (name, value),
+        (Some(Value::None), _) => {
+            return Err(Error::new(
+                ErrorKind::Value,
+                "(let) Cannot accept none as variable name!",
+            ))
+        }
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                "(let) Not enough arguments!",
+            ))
+        }
+    };
 
     let mut variables = VARIABLES
         .write()
         .expect("Error: Another user of this mutex panicked while holding the mutex!");
 
-    variables.insert(
-        var_name,
-        Variable {
-            value: Token {
-                ty: args[1].ty.clone(),
-                modifiers: args[1].modifiers.clone(),
-                val: var_value,
-            },
-            scope: *meta.scope,
-        },
-    );
+    variables.declare(var_name, var_value);
 
-    Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
-    })
+    Ok(Value::None)
 }
 
-fn sum(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
-
-    if args.len() < 2 {
-        return Err("(+) Not enough arguments!".to_string());
-    }
+/// `name = value`: changes the nearest variable called `name`, which has to exist already.
+fn assign(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let mut args = get_args(tokens, meta, false)?.into_iter();
 
-    let first = match args[0].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[0].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(+) Invalid type: Cannot use operation '+' with type {ty:?}"
-            ))
-        }
+    let (name, value) = match (args.next(), args.next()) {
+        (Some(Value::Str(name)), Some(value)) => (name, value),
+        _ => return Err(Error::new(ErrorKind::Argument, "(=) Not enough arguments!")),
     };
-    let second = match args[1].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[1].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(+) Invalid type: Cannot use operation '+' with type {ty:?}"
+
+    match VARIABLES
+        .write()
+        .unwrap()
+        .with_mut(&name, |variable| *variable = value)
+    {
+        Some(()) => {}
+        None => {
+            return Err(Error::new(
+                ErrorKind::Name,
+                format!("(=) Variable `{name}` not found! Use `let` to create it."),
             ))
         }
-    };
+    }
 
-    let total = first + second;
+    Ok(Value::None)
+}
 
-    if total.fract() == 0.0 {
-        let total = total as i64;
+fn sum(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-        return Ok(Token {
-            ty: TokenTypes::INT,
-            modifiers: vec![],
-            val: total.to_string(),
-        });
-    } else {
-        return Ok(Token {
-            ty: TokenTypes::FLOAT,
-            modifiers: vec![],
-            val: total.to_string(),
-        });
+    if args.len() < 2 {
+        return Err(Error::new(ErrorKind::Argument, "(+) Not enough arguments!"));
     }
+
+    add(&args[0], &args[1], "+")
 }
-fn difference(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+fn difference(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
     if args.len() < 2 {
-        return Err("(-) Not enough arguments!".to_string());
+        return Err(Error::new(ErrorKind::Argument, "(-) Not enough arguments!"));
     }
 
-    let first = match args[0].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[0].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(-) Invalid type: Cannot use operation '-' with type {ty:?}"
-            ))
-        }
-    };
-    let second = match args[1].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[1].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(-) Invalid type: Cannot use operation '-' with type {ty:?}"
-            ))
-        }
-    };
-
-    let difference = first - second;
-
-    if difference.fract() == 0.0 {
-        let difference = difference as i64;
+    calculate(&args[0], &args[1], "-", "-")
+}
+fn product(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-        return Ok(Token {
-            ty: TokenTypes::INT,
-            modifiers: vec![],
-            val: difference.to_string(),
-        });
-    } else {
-        return Ok(Token {
-            ty: TokenTypes::FLOAT,
-            modifiers: vec![],
-            val: difference.to_string(),
-        });
+    if args.len() < 2 {
+        return Err(Error::new(ErrorKind::Argument, "(*) Not enough arguments!"));
     }
+
+    calculate(&args[0], &args[1], "*", "*")
 }
-fn product(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+
+/// `a / b`: always a float, even when both numbers are integers, so `6 / 2` is `3.0`. Use `//`
+/// for a whole number.
+fn quotient(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
     if args.len() < 2 {
-        return Err("(*) Not enough arguments!".to_string());
+        return Err(Error::new(ErrorKind::Argument, "(/) Not enough arguments!"));
     }
 
-    let first = match args[0].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[0].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(*) Invalid type: Cannot use operation '*' with type {ty:?}"
-            ))
-        }
-    };
-    let second = match args[1].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[1].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(*) Invalid type: Cannot use operation '*' with type {ty:?}"
-            ))
-        }
-    };
-
-    let product = first * second;
+    calculate(&args[0], &args[1], "/", "/")
+}
 
-    if product.fract() == 0.0 {
-        let product = product as i64;
+/// `a // b`: divides and drops what is after the decimal point, rounding toward zero like `%`,
+/// so `7 // 2` is `3` and `-7 // 2` is `-3`. Gives an integer for two integers, and a whole float
+/// otherwise.
+fn int_quotient(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-        return Ok(Token {
-            ty: TokenTypes::INT,
-            modifiers: vec![],
-            val: product.to_string(),
-        });
-    } else {
-        return Ok(Token {
-            ty: TokenTypes::FLOAT,
-            modifiers: vec![],
-            val: product.to_string(),
-        });
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(//) Not enough arguments!",
+        ));
     }
+
+    calculate(&args[0], &args[1], "//", "//")
 }
-fn quotient(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+
+fn remainder(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
     if args.len() < 2 {
-        return Err("(/) Not enough arguments!".to_string());
+        return Err(Error::new(ErrorKind::Argument, "(%) Not enough arguments!"));
     }
 
-    let first = match args[0].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[0].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(/) Invalid type: Cannot use operation '/' with type {ty:?}"
-            ))
-        }
-    };
-    let second = match args[1].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[1].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(/) Invalid type: Cannot use operation '/' with type {ty:?}"
-            ))
+    calculate(&args[0], &args[1], "%", "%")
+}
+
+/// Does the arithmetic operation `op` for `fname`.
+///
+/// Two integers give an integer, worked out exactly: a result that does not fit in 64 bits
+/// becomes a big integer instead of being rounded. The exception is `/`, which gives a float.
+/// An integer and a decimal, or two decimals, give an exact decimal, even for `/`. Otherwise, if
+/// either number is a float, both are used as floats and the result is a float, even if it is
+/// whole. A decimal and a float cannot be mixed, so that money is never rounded by accident.
+/// Dividing by zero is an error for every kind of number.
+fn calculate(first: &Value, second: &Value, op: &str, fname: &str) -> Result<Value, Error> {
+    let divides = matches!(op, "/" | "//" | "%");
+
+    if let (Value::Int(a), Value::Int(b)) = (first, second) {
+        if divides && *b == 0 {
+            return Err(zero_division(op, fname));
         }
-    };
 
-    let quotient = first / second;
+        let result = match op {
+            "+" => a.checked_add(*b),
+            "-" => a.checked_sub(*b),
+            "*" => a.checked_mul(*b),
+            "/" => return Ok(Value::Float(*a as f64 / *b as f64)),
+            "//" => a.checked_div(*b),
+            "%" => a.checked_rem(*b),
+            _ => unreachable!("`{op}` is not an arithmetic operation"),
+        };
 
-    if quotient.fract() == 0.0 {
-        let quotient = quotient as i64;
+        // A result that does not fit in 64 bits is worked out again with big integers.
+        if let Some(result) = result {
+            return Ok(Value::Int(result));
+        }
+    }
 
-        return Ok(Token {
-            ty: TokenTypes::INT,
-            modifiers: vec![],
-            val: quotient.to_string(),
-        });
-    } else {
-        return Ok(Token {
-            ty: TokenTypes::FLOAT,
-            modifiers: vec![],
-            val: quotient.to_string(),
+    if let (Some(a), Some(b)) = (big_int(first), big_int(second)) {
+        if divides && b.is_zero() {
+            return Err(zero_division(op, fname));
+        }
+
+        return Ok(match op {
+            "+" => Value::integer(a + b),
+            "-" => Value::integer(a - b),
+            "*" => Value::integer(a * b),
+            "/" => Value::Float(number_arg(first, fname)? / number_arg(second, fname)?),
+            "//" => Value::integer(a / b),
+            "%" => Value::integer(a % b),
+            _ => unreachable!("`{op}` is not an arithmetic operation"),
         });
     }
-}
 
-fn forever(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+    if matches!(first, Value::Decimal(_)) || matches!(second, Value::Decimal(_)) {
+        let a = decimal_arg(first, fname)?;
+        let b = decimal_arg(second, fname)?;
+
+        if divides && b.is_zero() {
+            return Err(zero_division(op, fname));
+        }
 
-    if args.len() < 1 {
-        return Err("(forever) Not enough arguments!".to_string());
+        return Ok(Value::Decimal(match op {
+            "+" => a + b,
+            "-" => a - b,
+            "*" => a * b,
+            "/" => a / b,
+            "//" => (a / b).with_scale_round(0, RoundingMode::Down),
+            "%" => a % b,
+            _ => unreachable!("`{op}` is not an arithmetic operation"),
+        }));
     }
 
-    let scope = args[0].clone();
+    let a = number_arg(first, fname)?;
+    let b = number_arg(second, fname)?;
 
-    loop {
-        let stat = run_scope(&scope, meta);
-
-        if (stat
-            == Token {
-                ty: TokenTypes::FUNC,
-                modifiers: vec![],
-                val: "break".to_string(),
-            })
-        {
-            break;
-        }
+    if divides && b == 0.0 {
+        return Err(zero_division(op, fname));
     }
 
-    Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
-    })
+    Ok(Value::Float(match op {
+        "+" => a + b,
+        "-" => a - b,
+        "*" => a * b,
+        "/" => a / b,
+        "//" => (a / b).trunc(),
+        "%" => a % b,
+        _ => unreachable!("`{op}` is not an arithmetic operation"),
+    }))
+}
+
+/// Adds two values for `fname`. Two strings are joined into one, and numbers are added by
+/// [`calculate`].
+fn add(first: &Value, second: &Value, fname: &str) -> Result<Value, Error> {
+    match (first, second) {
+        (Value::Str(a), Value::Str(b)) => Ok(Value::Str(format!("{a}{b}"))),
+        (Value::Str(_), value) | (value, Value::Str(_)) => Err(Error::new(
+            ErrorKind::Type,
+            format!("({fname}) Cannot add type {:?} to a string!", value.ty()),
+        )),
+        _ => calculate(first, second, "+", fname),
+    }
+}
+
+/// The error for dividing by zero with `op`.
+fn zero_division(op: &str, fname: &str) -> Error {
+    let message = match op {
+        "%" => format!("({fname}) Cannot take the remainder of division by zero!"),
+        _ => format!("({fname}) Cannot divide by zero!"),
+    };
+
+    Error::new(ErrorKind::ZeroDivision, message)
 }
 
-fn scope(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+/// Compares two numbers for `op`. Integers and decimals are compared exactly, and a float with
+/// anything else is compared as floats. Gives `None` if either is `NaN`.
+fn compare(first: &Value, second: &Value, op: &str) -> Result<Option<Ordering>, Error> {
+    if let (Value::Int(a), Value::Int(b)) = (first, second) {
+        return Ok(Some(a.cmp(b)));
+    }
 
-    if args.len() < 1 {
-        return Err("(scope) Not enough arguments!".to_string());
+    if let (Some(a), Some(b)) = (big_int(first), big_int(second)) {
+        return Ok(Some(a.cmp(&b)));
     }
 
-    let scope = args[0].clone();
-    let stat = run_scope(&scope, meta);
+    if let (Ok(a), Ok(b)) = (decimal_arg(first, op), decimal_arg(second, op)) {
+        return Ok(a.partial_cmp(&b));
+    }
 
-    if (stat
-        == Token {
-            ty: TokenTypes::FUNC,
-            modifiers: vec![],
-            val: "break".to_string(),
-        })
-    {
-        return Ok(Token {
-            ty: TokenTypes::NONE,
-            modifiers: vec![],
-            val: "None".to_string(),
-        });
+    Ok(number_arg(first, op)?.partial_cmp(&number_arg(second, op)?))
+}
+
+/// Whether two values are equal for `==` and `!=`. Numbers are equal when they are the same
+/// number, whatever their types, so `1 == 1.0`.
+fn equal(first: &Value, second: &Value) -> bool {
+    if first.is_number() && second.is_number() {
+        return compare(first, second, "==") == Ok(Some(Ordering::Equal));
     }
 
-    Ok(stat)
+    first == second
 }
 
-fn if_(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+/// Reads a number given to the operation `op`.
+fn number_arg(value: &Value, op: &str) -> Result<f64, Error> {
+    match value {
+        Value::Int(int) => Ok(*int as f64),
+        Value::BigInt(int) => Ok(int.to_f64().unwrap_or(f64::NAN)),
+        Value::Float(float) => Ok(*float),
+        Value::Decimal(decimal) => Ok(decimal.to_f64().unwrap_or(f64::NAN)),
+        value => Err(Error::new(
+            ErrorKind::Type,
+            format!(
+                "({op}) Invalid type: Cannot use operation '{op}' with type {:?}",
+                value.ty()
+            ),
+        )),
+    }
+}
 
-    if args.len() < 2 {
-        return Err("(if) Not enough arguments!".to_string());
+/// Reads an integer of any size, if `value` is one.
+fn big_int(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::Int(int) => Some(BigInt::from(*int)),
+        Value::BigInt(int) => Some(int.clone()),
+        _ => None,
     }
+}
 
-    let condition = args[0].clone();
+/// Reads a number given to `fname` as an exact decimal. Floats are not read, since they are
+/// already rounded.
+fn decimal_arg(value: &Value, fname: &str) -> Result<BigDecimal, Error> {
+    match value {
+        Value::Int(int) => Ok(BigDecimal::from(*int)),
+        Value::BigInt(int) => Ok(BigDecimal::from(int.clone())),
+        Value::Decimal(decimal) => Ok(decimal.clone()),
+        Value::Float(_) => Err(Error::new(ErrorKind::Type, format!(
+            "({fname}) Cannot mix a decimal with a float! Use `decimal` or `float` to convert one of them."
+        ))),
+        value => Err(Error::new(ErrorKind::Type, format!(
+            "({fname}) Invalid type: Cannot use operation '{fname}' with type {:?}",
+            value.ty()
+        ))),
+    }
+}
 
-    let result = match condition.ty {
-        TokenTypes::BOOL => condition.val,
-        ty => return Err(format!("(if) Type {ty:?} cannot be used as condition!")),
+fn forever(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let scope = match tokens.first() {
+        Some(scope) => scope,
+        None => {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                "(forever) Not enough arguments!",
+            ))
+        }
     };
 
-    if result == "false" {
-        return Ok(Token {
-            ty: TokenTypes::NONE,
-            modifiers: vec![],
-            val: "None".to_string(),
-        });
+    loop {
+        match run_loop_body(scope, meta)? {
+            Flow::Break => break,
+            Flow::Return(value) => return Ok(pass_on(Flow::Return(value), meta)),
+            Flow::Normal(_) | Flow::Continue => {}
+        }
     }
 
-    let ret = run_scope(&args[1], meta);
-
-    Ok(ret)
+    Ok(Value::None)
 }
 
-fn eq(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+fn scope(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let scope = match tokens.first() {
+        Some(scope) => scope,
+        None => {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                "(scope) Not enough arguments!",
+            ))
+        }
+    };
 
-    if args.len() < 2 {
-        return Err("(==) Not enough arguments!".to_string());
+    match run_loop_body(scope, meta)? {
+        Flow::Break | Flow::Continue => Ok(Value::None),
+        flow => Ok(pass_on(flow, meta)),
     }
+}
 
-    let first = &args[0];
-    let second = &args[1];
+/// `if c { ... } else if d { ... } else { ... }`: runs the first branch whose condition is
+/// `true`. Conditions are only run until one of them is `true`.
+fn if_(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let mut tokens = tokens.into_iter();
 
-    if (first.ty == second.ty) && (first.val == second.val) {
-        return Ok(Token {
-            ty: TokenTypes::BOOL,
-            modifiers: vec![],
-            val: "true".to_string(),
-        });
-    }
+    loop {
+        let (condition, scope) = match (tokens.next(), tokens.next()) {
+            (Some(condition), Some(scope)) => (condition, scope),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Argument,
+                    "(if) Not enough arguments!",
+                ))
+            }
+        };
 
-    return Ok(Token {
-        ty: TokenTypes::BOOL,
-        modifiers: vec![],
-        val: "false".to_string(),
-    });
+        if condition_arg(&get_arg(condition, meta, false)?, "if")? {
+            let flow = run_scope(&scope, meta)?;
+            return Ok(pass_on(flow, meta));
+        }
+
+        match tokens.next() {
+            None => return Ok(Value::None),
+            Some(token) if token.ty == TokenTypes::NAME && token.val == "else if" => continue,
+            Some(token) if token.ty == TokenTypes::NAME && token.val == "else" => {
+                return match (tokens.next(), tokens.next()) {
+                    (Some(scope), None) if scope.ty == TokenTypes::SCOPE => {
+                        let flow = run_scope(&scope, meta)?;
+                        Ok(pass_on(flow, meta))
+                    }
+                    _ => Err(Error::new(
+                        ErrorKind::Syntax,
+                        "(if) Expected a single scope after `else`!",
+                    )),
+                }
+            }
+            Some(_) => {
+                return Err(Error::new(
+                    ErrorKind::Syntax,
+                    "(if) Expected `else` or `else if` after the scope!",
+                ))
+            }
+        }
+    }
 }
 
-fn ne(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+fn eq(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
     if args.len() < 2 {
-        return Err("(!=) Not enough arguments!".to_string());
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(==) Not enough arguments!",
+        ));
     }
 
-    let first = &args[0];
-    let second = &args[1];
+    Ok(Value::Bool(equal(&args[0], &args[1])))
+}
+
+fn ne(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-    if (first.ty == second.ty) && (first.val == second.val) {
-        return Ok(Token {
-            ty: TokenTypes::BOOL,
-            modifiers: vec![],
-            val: "false".to_string(),
-        });
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(!=) Not enough arguments!",
+        ));
     }
 
-    return Ok(Token {
-        ty: TokenTypes::BOOL,
-        modifiers: vec![],
-        val: "true".to_string(),
-    });
+    Ok(Value::Bool(!equal(&args[0], &args[1])))
 }
 
-fn lazy_eq(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+/// `a lazy= b`: whether `a` and `b` are shown the same way, whatever their types.
+fn lazy_eq(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
     if args.len() < 2 {
-        return Err("(lazy=) Not enough arguments!".to_string());
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(lazy=) Not enough arguments!",
+        ));
     }
 
-    let first = &args[0].val;
-    let second = &args[1].val;
+    Ok(Value::Bool(args[0].to_string() == args[1].to_string()))
+}
 
-    if first == second {
-        return Ok(Token {
-            ty: TokenTypes::BOOL,
-            modifiers: vec![],
-            val: "true".to_string(),
-        });
+fn lazy_ne(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(lazy!=) Not enough arguments!",
+        ));
     }
 
-    return Ok(Token {
-        ty: TokenTypes::BOOL,
-        modifiers: vec![],
-        val: "false".to_string(),
-    });
+    Ok(Value::Bool(args[0].to_string() != args[1].to_string()))
 }
 
-fn lazy_ne(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+fn gt(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
     if args.len() < 2 {
-        return Err("(lazy!=) Not enough arguments!".to_string());
+        return Err(Error::new(ErrorKind::Argument, "(>) Not enough arguments!"));
     }
 
-    let first = &args[0].val;
-    let second = &args[1].val;
+    Ok(Value::Bool(
+        compare(&args[0], &args[1], ">")? == Some(Ordering::Greater),
+    ))
+}
+fn lt(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-    if first == second {
-        return Ok(Token {
-            ty: TokenTypes::BOOL,
-            modifiers: vec![],
-            val: "false".to_string(),
-        });
+    if args.len() < 2 {
+        return Err(Error::new(ErrorKind::Argument, "(<) Not enough arguments!"));
     }
 
-    return Ok(Token {
-        ty: TokenTypes::BOOL,
-        modifiers: vec![],
-        val: "true".to_string(),
-    });
+    Ok(Value::Bool(
+        compare(&args[0], &args[1], "<")? == Some(Ordering::Less),
+    ))
 }
 
-fn gt(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+fn ge(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
     if args.len() < 2 {
-        return Err("(>) Not enough arguments!".to_string());
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(>=) Not enough arguments!",
+        ));
     }
 
-    let first = match args[0].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[0].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(>) Invalid type: Cannot use operation '>' with type {ty:?}"
-            ))
-        }
-    };
-    let second = match args[1].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[1].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(>) Invalid type: Cannot use operation '>' with type {ty:?}"
-            ))
-        }
-    };
+    Ok(Value::Bool(matches!(
+        compare(&args[0], &args[1], ">=")?,
+        Some(Ordering::Greater | Ordering::Equal)
+    )))
+}
+fn le(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-    if first > second {
-        return Ok(Token {
-            ty: TokenTypes::BOOL,
-            modifiers: vec![],
-            val: "true".to_string(),
-        });
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(<=) Not enough arguments!",
+        ));
     }
 
-    return Ok(Token {
-        ty: TokenTypes::BOOL,
-        modifiers: vec![],
-        val: "false".to_string(),
-    });
+    Ok(Value::Bool(matches!(
+        compare(&args[0], &args[1], "<=")?,
+        Some(Ordering::Less | Ordering::Equal)
+    )))
 }
-fn lt(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
 
-    if args.len() < 2 {
-        return Err("(<) Not enough arguments!".to_string());
+/// Gets the value of a condition, for `and`, `or` and `not`.
+fn truth(value: &Value, op: &str) -> Result<bool, Error> {
+    match value {
+        Value::Bool(bool) => Ok(*bool),
+        value => Err(Error::new(
+            ErrorKind::Type,
+            format!(
+                "({op}) Invalid type: Cannot use operation '{op}' with type {:?}",
+                value.ty()
+            ),
+        )),
     }
+}
 
-    let first = match args[0].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[0].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(<) Invalid type: Cannot use operation '<' with type {ty:?}"
-            ))
-        }
-    };
-    let second = match args[1].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => args[1].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(<) Invalid type: Cannot use operation '<' with type {ty:?}"
-            ))
-        }
-    };
+/// Gets the value of the condition of `if`, `while` or `until`.
+fn condition_arg(value: &Value, fname: &str) -> Result<bool, Error> {
+    match value {
+        Value::Bool(bool) => Ok(*bool),
+        value => Err(Error::new(
+            ErrorKind::Type,
+            format!(
+                "({fname}) Type {:?} cannot be used as condition!",
+                value.ty()
+            ),
+        )),
+    }
+}
 
-    if first < second {
-        return Ok(Token {
-            ty: TokenTypes::BOOL,
-            modifiers: vec![],
-            val: "true".to_string(),
-        });
+/// `a and b`: the second value is only run if the first one is `true`.
+fn logical_and(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    if tokens.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(and) Not enough arguments!",
+        ));
     }
 
-    return Ok(Token {
-        ty: TokenTypes::BOOL,
-        modifiers: vec![],
-        val: "false".to_string(),
-    });
+    let result = truth(&get_arg(tokens[0].clone(), meta, false)?, "and")?
+        && truth(&get_arg(tokens[1].clone(), meta, false)?, "and")?;
+
+    Ok(Value::Bool(result))
 }
 
-fn while_(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    loop {
-        let args = get_args(tokens.clone(), meta, false);
+/// `a or b`: the second value is only run if the first one is `false`.
+fn logical_or(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    if tokens.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(or) Not enough arguments!",
+        ));
+    }
 
-        if args.len() < 2 {
-            return Err("(while) Not enough arguments!".to_string());
-        }
+    let result = truth(&get_arg(tokens[0].clone(), meta, false)?, "or")?
+        || truth(&get_arg(tokens[1].clone(), meta, false)?, "or")?;
 
-        let condition = args[0].clone();
-        let result = match condition.ty {
-            TokenTypes::BOOL => condition.val,
-            ty => return Err(format!("(while) Type {ty:?} cannot be used as condition!")),
-        };
+    Ok(Value::Bool(result))
+}
 
-        if result == "false" {
-            return Ok(Token {
-                ty: TokenTypes::NONE,
-                modifiers: vec![],
-                val: "None".to_string(),
-            });
-        }
+fn logical_not(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-        let scope = args[1].clone();
-        let stat = run_scope(&scope, meta);
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(not) Not enough arguments!",
+        ));
+    }
 
-        if (stat
-            == Token {
-                ty: TokenTypes::FUNC,
-                modifiers: vec![],
-                val: "break".to_string(),
-            })
-        {
-            return Ok(Token {
-                ty: TokenTypes::NONE,
-                modifiers: vec![],
-                val: "None".to_string(),
-            });
+    Ok(Value::Bool(!truth(&args[0], "not")?))
+}
+
+fn while_(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    if tokens.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(while) Not enough arguments!",
+        ));
+    }
+
+    loop {
+        if !condition_arg(&get_arg(tokens[0].clone(), meta, false)?, "while")? {
+            return Ok(Value::None);
+        }
+
+        match run_loop_body(&tokens[1], meta)? {
+            Flow::Break => return Ok(Value::None),
+            Flow::Return(value) => return Ok(pass_on(Flow::Return(value), meta)),
+            Flow::Normal(_) | Flow::Continue => {}
         }
     }
 }
 
-fn until(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
+fn until(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    if tokens.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(until) Not enough arguments!",
+        ));
+    }
+
     loop {
-        let args = get_args(tokens.clone(), meta, false);
+        if condition_arg(&get_arg(tokens[0].clone(), meta, false)?, "until")? {
+            return Ok(Value::None);
+        }
 
-        if args.len() < 2 {
-            return Err("(until) Not enough arguments!".to_string());
+        match run_loop_body(&tokens[1], meta)? {
+            Flow::Break => return Ok(Value::None),
+            Flow::Return(value) => return Ok(pass_on(Flow::Return(value), meta)),
+            Flow::Normal(_) | Flow::Continue => {}
         }
+    }
+}
+
+/// `for name in values { ... }`: runs the scope once for every item of an array, every character
+/// of a string, every key of a map, or every number of `range start end step`, with `name` set to
+/// it. `for key value in map { ... }` also sets `value` to the key's value.
+fn for_(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let usage = "(for) Expected `for name in values { ... }`!";
+
+    let (scope, header) = match tokens.split_last() {
+        Some((scope, header)) if scope.ty == TokenTypes::SCOPE && header.len() >= 3 => {
+            (scope, header)
+        }
+        _ => return Err(Error::new(ErrorKind::Syntax, usage)),
+    };
+
+    let (names, values) = match header.iter().take(3).position(|token| token.val == "in") {
+        Some(i) if i > 0 && i + 1 < header.len() => (&header[..i], &header[i + 1..]),
+        _ => return Err(Error::new(ErrorKind::Syntax, usage)),
+    };
+
+    let names: Vec<String> = names.iter().map(|name| name.val.to_string()).collect();
+
+    // The items are made one at a time, so a long `range` is never held all at once.
+    let items: Box<dyn Iterator<Item = Vec<Value>>> =
+        if values[0].ty == TokenTypes::NAME && values[0].val == "range" {
+            Box::new(range(&values[1..], meta)?.map(|item| vec![item]))
+        } else {
+            if values.len() > 1 {
+                return Err(Error::new(ErrorKind::Syntax, usage));
+            }
+
+            let values = get_arg(values[0].clone(), meta, false)?;
 
-        let condition = args[0].clone();
-        let result = match condition.ty {
-            TokenTypes::BOOL => condition.val,
-            ty => return Err(format!("(until) Type {ty:?} cannot be used as condition!")),
+            match values {
+                Value::Map(entries) => {
+                    Box::new(entries.into_iter().map(|(key, value)| vec![key, value]))
+                }
+                Value::Array(items) => Box::new(items.into_iter().map(|item| vec![item])),
+                Value::Str(string) => Box::new(
+                    string
+                        .chars()
+                        .map(|c| vec![Value::Str(c.to_string())])
+                        .collect::<Vec<Vec<Value>>>()
+                        .into_iter(),
+                ),
+                value => {
+                    return Err(Error::new(
+                        ErrorKind::Type,
+                        format!("(for) Cannot loop over type {:?}!", value.ty()),
+                    ))
+                }
+            }
         };
+    let mut items = items.peekable();
+
+    if names.len() > 1 && items.peek().is_some_and(|item| item.len() < names.len()) {
+        return Err(Error::new(
+            ErrorKind::Syntax,
+            "(for) Only maps can be looped over with two names!",
+        ));
+    }
 
-        if result == "true" {
-            return Ok(Token {
-                ty: TokenTypes::NONE,
-                modifiers: vec![],
-                val: "None".to_string(),
-            });
+    for item in items {
+        let locals = names.iter().cloned().zip(item).collect();
+        bind_locals(locals, None);
+        let flow = run_loop_body(scope, meta);
+        unbind_locals();
+
+        match flow? {
+            Flow::Break => break,
+            Flow::Return(value) => {
+                *meta.flow = Some(Flow::Return(value));
+                break;
+            }
+            Flow::Normal(_) | Flow::Continue => {}
         }
+    }
+
+    Ok(Value::None)
+}
 
-        let scope = args[1].clone();
-        let stat = run_scope(&scope, meta);
+/// The numbers of `range end`, `range start end` or `range start end step`, not including `end`.
+/// They are made as they are needed, and end early rather than go past the largest or smallest
+/// integer.
+fn range(tokens: &[Token], meta: &mut Metadata) -> Result<impl Iterator<Item = Value>, Error> {
+    let mut bounds: Vec<i64> = vec![];
 
-        if (stat
-            == Token {
-                ty: TokenTypes::FUNC,
-                modifiers: vec![],
-                val: "break".to_string(),
-            })
-        {
-            return Ok(Token {
-                ty: TokenTypes::NONE,
-                modifiers: vec![],
-                val: "None".to_string(),
-            });
+    for token in tokens {
+        match get_arg(token.clone(), meta, false)? {
+            Value::Int(bound) => bounds.push(bound),
+            bound => {
+                return Err(Error::new(
+                    ErrorKind::Type,
+                    format!(
+                        "(range) Invalid type: Expected integers, got {:?}",
+                        bound.ty()
+                    ),
+                ))
+            }
+        }
+    }
+
+    let (start, end, step) = match bounds[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Syntax,
+                "(range) Expected `range end`, `range start end` or `range start end step`!"
+                    .to_string(),
+            ))
         }
+    };
+
+    if step == 0 {
+        return Err(Error::new(ErrorKind::Value, "(range) Step cannot be zero!"));
     }
+
+    Ok(iter::successors(Some(start), move |n| n.checked_add(step))
+        .take_while(move |n| (step > 0 && *n < end) || (step < 0 && *n > end))
+        .map(Value::Int))
 }
 
-fn add_assign(tokens: Vec<Token>, _meta: &mut Metadata) -> Result<Token, String> {
+fn add_assign(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
     if tokens.len() < 2 {
-        return Err("(+=) Not enough arguments!".to_string());
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(+=) Not enough arguments!",
+        ));
     }
 
     let first = match tokens[0].ty.clone() {
         TokenTypes::VARIABLE => &tokens[0].val,
         ty => {
-            return Err(format!(
-                "(+=) Invalid type: Cannot use operation '+=' with type {ty:?}"
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(+=) Invalid type: Cannot use operation '+=' with type {ty:?}"),
             ))
         }
     };
-    let second = match tokens[1].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => tokens[1].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(+=) Invalid type: Cannot add thing of type {ty:?} to variable"
+    let second = match get_arg(tokens[1].clone(), meta, false)? {
+        second if second.is_number() || matches!(second, Value::Str(_)) => second,
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(+=) Invalid type: Cannot add thing of type {:?} to variable",
+                    value.ty()
+                ),
             ))
         }
     };
 
-    let mut variables = VARIABLES.write().unwrap();
+    let changed = VARIABLES.write().unwrap().with_mut(first, |variable| {
+        if !variable.is_number() && !matches!(variable, Value::Str(_)) {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(+=) Invalid type: Cannot use operation '+=' with variable of type {:?}",
+                    variable.ty()
+                ),
+            ));
+        }
 
-    let variable = variables.get_mut(first);
+        *variable = add(variable, &second, "+=")?;
+        Ok(())
+    });
 
-    let variable = match variable {
-        Some(v) => v,
-        None => return Err(format!("(+=) Variable `{first}` not found")),
-    };
+    match changed {
+        Some(changed) => changed?,
+        None => {
+            return Err(Error::new(
+                ErrorKind::Name,
+                format!("(+=) Variable `{first}` not found"),
+            ))
+        }
+    }
 
-    let value: f64 = match &variable.value.ty {
-        TokenTypes::INT => variable.value.val.parse().unwrap(),
-        TokenTypes::FLOAT => variable.value.val.parse().unwrap(),
+    Ok(Value::None)
+}
+fn subtract_assign(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    if tokens.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(-=) Not enough arguments!",
+        ));
+    }
+
+    let first = match tokens[0].ty.clone() {
+        TokenTypes::VARIABLE => &tokens[0].val,
         ty => {
-            return Err(format!(
-                "(+=) Invalid type: Cannot use operation '+=' with variable of type {ty:?}"
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(-=) Invalid type: Cannot use operation '-=' with type {ty:?}"),
+            ))
+        }
+    };
+    let second = match get_arg(tokens[1].clone(), meta, false)? {
+        second if second.is_number() => second,
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(-=) Invalid type: Cannot subtract thing of type {:?} from variable",
+                    value.ty()
+                ),
             ))
         }
     };
 
-    let total = value + second;
+    let changed = VARIABLES.write().unwrap().with_mut(first, |variable| {
+        if !variable.is_number() {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(-=) Invalid type: Cannot use operation '-=' with variable of type {:?}",
+                    variable.ty()
+                ),
+            ));
+        }
 
-    if total.fract() == 0.0 {
-        let total = total as i64;
+        *variable = calculate(variable, &second, "-", "-=")?;
+        Ok(())
+    });
 
-        *variable = Variable {
-            value: Token {
-                ty: TokenTypes::INT,
-                modifiers: variable.value.modifiers.clone(),
-                val: total.to_string(),
-            },
-            scope: variable.scope,
-        };
-    } else {
-        *variable = Variable {
-            value: Token {
-                ty: TokenTypes::FLOAT,
-                modifiers: variable.value.modifiers.clone(),
-                val: total.to_string(),
-            },
-            scope: variable.scope,
-        };
+    match changed {
+        Some(changed) => changed?,
+        None => {
+            return Err(Error::new(
+                ErrorKind::Name,
+                format!("(-=) Variable `{first}` not found"),
+            ))
+        }
     }
 
-    return Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
-    });
+    Ok(Value::None)
 }
-fn subtract_assign(tokens: Vec<Token>, _meta: &mut Metadata) -> Result<Token, String> {
+
+fn multiply_assign(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
     if tokens.len() < 2 {
-        return Err("(-=) Not enough arguments!".to_string());
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(*=) Not enough arguments!",
+        ));
     }
 
     let first = match tokens[0].ty.clone() {
         TokenTypes::VARIABLE => &tokens[0].val,
         ty => {
-            return Err(format!(
-                "(-=) Invalid type: Cannot use operation '-=' with type {ty:?}"
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(*=) Invalid type: Cannot use operation '*=' with type {ty:?}"),
             ))
         }
     };
-    let second = match tokens[1].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => tokens[1].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(-=) Invalid type: Cannot subtract thing of type {ty:?} from variable"
+    let second = match get_arg(tokens[1].clone(), meta, false)? {
+        second if second.is_number() => second,
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(*=) Invalid type: Cannot multiply thing of type {:?} with variable",
+                    value.ty()
+                ),
             ))
         }
     };
 
-    let mut variables = VARIABLES.write().unwrap();
+    let changed = VARIABLES.write().unwrap().with_mut(first, |variable| {
+        if !variable.is_number() {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(*=) Invalid type: Cannot use operation '*=' with variable of type {:?}",
+                    variable.ty()
+                ),
+            ));
+        }
 
-    let variable = variables.get_mut(first);
+        *variable = calculate(variable, &second, "*", "*=")?;
+        Ok(())
+    });
 
-    let variable = match variable {
-        Some(v) => v,
-        None => return Err(format!("(-=) Variable `{first}` not found")),
-    };
+    match changed {
+        Some(changed) => changed?,
+        None => {
+            return Err(Error::new(
+                ErrorKind::Name,
+                format!("(*=) Variable `{first}` not found"),
+            ))
+        }
+    }
 
-    let value: f64 = match &variable.value.ty {
-        TokenTypes::INT => variable.value.val.parse().unwrap(),
-        TokenTypes::FLOAT => variable.value.val.parse().unwrap(),
+    Ok(Value::None)
+}
+
+fn divide_assign(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    if tokens.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(/=) Not enough arguments!",
+        ));
+    }
+
+    let first = match tokens[0].ty.clone() {
+        TokenTypes::VARIABLE => &tokens[0].val,
         ty => {
-            return Err(format!(
-                "(-=) Invalid type: Cannot use operation '-=' with variable of type {ty:?}"
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(/=) Invalid type: Cannot use operation '/=' with type {ty:?}"),
+            ))
+        }
+    };
+    let second = match get_arg(tokens[1].clone(), meta, false)? {
+        second if second.is_number() => second,
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(/=) Invalid type: Variable cannot be divided by thing of type {:?}",
+                    value.ty()
+                ),
             ))
         }
     };
 
-    let total = value - second;
+    let changed = VARIABLES.write().unwrap().with_mut(first, |variable| {
+        if !variable.is_number() {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(/=) Invalid type: Cannot use operation '/=' with variable of type {:?}",
+                    variable.ty()
+                ),
+            ));
+        }
 
-    if total.fract() == 0.0 {
-        let total = total as i64;
+        *variable = calculate(variable, &second, "/", "/=")?;
+        Ok(())
+    });
 
-        *variable = Variable {
-            value: Token {
-                ty: TokenTypes::INT,
-                modifiers: variable.value.modifiers.clone(),
-                val: total.to_string(),
-            },
-            scope: variable.scope,
-        };
-    } else {
-        *variable = Variable {
-            value: Token {
-                ty: TokenTypes::FLOAT,
-                modifiers: variable.value.modifiers.clone(),
-                val: total.to_string(),
-            },
-            scope: variable.scope,
+    match changed {
+        Some(changed) => changed?,
+        None => {
+            return Err(Error::new(
+                ErrorKind::Name,
+                format!("(/=) Variable `{first}` not found"),
+            ))
+        }
+    }
+
+    Ok(Value::None)
+}
+
+fn break_(_tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    if *meta.loop_depth == 0 {
+        return Err(Error::new(
+            ErrorKind::Syntax,
+            "(break) Can only be used inside a loop!",
+        ));
+    }
+
+    *meta.flow = Some(Flow::Break);
+
+    Ok(Value::None)
+}
+
+fn continue_(_tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    if *meta.loop_depth == 0 {
+        return Err(Error::new(
+            ErrorKind::Syntax,
+            "(continue) Can only be used inside a loop!",
+        ));
+    }
+
+    *meta.flow = Some(Flow::Continue);
+
+    Ok(Value::None)
+}
+
+fn return_(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    if *meta.call_depth == 0 {
+        return Err(Error::new(
+            ErrorKind::Syntax,
+            "(return) Can only be used inside a function!",
+        ));
+    }
+
+    let args = get_args(tokens, meta, false)?;
+
+    let value = args.into_iter().next().unwrap_or(Value::None);
+
+    *meta.flow = Some(Flow::Return(value.clone()));
+
+    Ok(value)
+}
+
+/// `try { ... } catch err { ... } finally { ... }`: runs the first scope, and if an error is
+/// raised inside it, runs the `catch` scope with `err` set to a map of the error's `kind` and
+/// `message`. The `finally` scope runs last, whether there was an error or not. The name after
+/// `catch`, and either `catch` or `finally` (but not both), can be left out.
+fn try_(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let usage = "(try) Expected `try { ... } catch name { ... } finally { ... }`!";
+
+    let mut tokens = tokens.into_iter().peekable();
+
+    let body = match tokens.next() {
+        Some(body) if body.ty == TokenTypes::SCOPE => body,
+        _ => return Err(Error::new(ErrorKind::Syntax, usage)),
+    };
+
+    let mut catch: Option<(Option<String>, Token)> = None;
+    let mut finally: Option<Token> = None;
+
+    if tokens.next_if(|token| token.val == "catch").is_some() {
+        let name = tokens
+            .next_if(|token| token.ty != TokenTypes::SCOPE)
+            .map(|name| name.val);
+
+        match tokens.next() {
+            Some(scope) if scope.ty == TokenTypes::SCOPE => catch = Some((name, scope)),
+            _ => return Err(Error::new(ErrorKind::Syntax, usage)),
+        }
+    }
+
+    if tokens.next_if(|token| token.val == "finally").is_some() {
+        match tokens.next() {
+            Some(scope) if scope.ty == TokenTypes::SCOPE => finally = Some(scope),
+            _ => return Err(Error::new(ErrorKind::Syntax, usage)),
+        }
+    }
+
+    if tokens.next().is_some() || (catch.is_none() && finally.is_none()) {
+        return Err(Error::new(ErrorKind::Syntax, usage));
+    }
+
+    let mut result = run_scope(&body, meta);
+
+    if let (Err(err), Some((name, scope))) = (&result, &catch) {
+        let locals = match name {
+            Some(name) => vec![(name.to_string(), error_value(err))],
+            None => vec![],
         };
+
+        bind_locals(locals, None);
+        result = run_scope(scope, meta);
+        unbind_locals();
     }
 
-    return Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
-    });
+    if let Some(scope) = &finally {
+        // An error, `break`, `continue` or `return` in `finally` replaces what came before it.
+        match run_scope(scope, meta)? {
+            Flow::Normal(_) => {}
+            flow => return Ok(pass_on(flow, meta)),
+        }
+    }
+
+    Ok(pass_on(result?, meta))
 }
 
-fn multiply_assign(tokens: Vec<Token>, _meta: &mut Metadata) -> Result<Token, String> {
-    if tokens.len() < 2 {
-        return Err("(*=) Not enough arguments!".to_string());
+/// `raise message` or `raise kind message`: raises an error that stops the program, unless a
+/// `try` around it catches it. The kind is `Error` if it is not given. `raise err` raises an
+/// error caught by `catch err` again.
+fn raise(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    let (kind, message) = match &args[..] {
+        [] => {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                "(raise) Not enough arguments!",
+            ))
+        }
+        [Value::Map(entries)] => {
+            let field = |name: &str| {
+                find_key(entries, &Value::Str(name.to_string())).map(|i| entries[i].1.to_string())
+            };
+
+            match (field("kind"), field("message")) {
+                (Some(kind), Some(message)) => (kind, message),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::Type,
+                        "(raise) Expected a map with a `kind` and a `message`!",
+                    ))
+                }
+            }
+        }
+        [message] => ("Error".to_string(), message.to_string()),
+        [kind, message] => (kind.to_string(), message.to_string()),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                "(raise) Too many arguments!",
+            ))
+        }
+    };
+
+    Err(Error::new(ErrorKind::Custom(kind), message))
+}
+
+/// The value that `catch` gives its name for `err`.
+fn error_value(err: &Error) -> Value {
+    Value::Map(vec![
+        (
+            Value::Str("kind".to_string()),
+            Value::Str(err.kind.to_string()),
+        ),
+        (
+            Value::Str("message".to_string()),
+            Value::Str(err.message.to_string()),
+        ),
+    ])
+}
+
+fn int(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(int) Not enough arguments!",
+        ));
     }
 
-    let first = match tokens[0].ty.clone() {
-        TokenTypes::VARIABLE => &tokens[0].val,
-        ty => {
-            return Err(format!(
-                "(*=) Invalid type: Cannot use operation '*=' with type {ty:?}"
+    match &args[0] {
+        Value::Int(int) => Ok(Value::Int(*int)),
+        Value::BigInt(int) => Ok(Value::BigInt(int.clone())),
+        Value::Float(float) if float.is_finite() => match BigInt::from_f64(float.trunc()) {
+            Some(int) => Ok(Value::integer(int)),
+            None => Err(Error::new(
+                ErrorKind::Value,
+                "(int) Could not convert value to integer",
+            )),
+        },
+        Value::Decimal(decimal) => {
+            let (int, _) = decimal
+                .with_scale_round(0, RoundingMode::Down)
+                .into_bigint_and_exponent();
+            Ok(Value::integer(int))
+        }
+        Value::Str(string) => match string.trim().parse::<BigInt>() {
+            Ok(int) => Ok(Value::integer(int)),
+            Err(_) => Err(Error::new(
+                ErrorKind::Value,
+                "(int) Could not convert value to integer",
+            )),
+        },
+        _ => Err(Error::new(
+            ErrorKind::Value,
+            "(int) Could not convert value to integer",
+        )),
+    }
+}
+fn float(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(float) Not enough arguments!",
+        ));
+    }
+
+    match &args[0] {
+        Value::Str(string) => match string.trim().parse::<f64>() {
+            Ok(float) => Ok(Value::Float(float)),
+            Err(_) => Err(Error::new(
+                ErrorKind::Value,
+                "(float) Could not convert value to float",
+            )),
+        },
+        value if value.is_number() => Ok(Value::Float(number_arg(value, "float")?)),
+        _ => Err(Error::new(
+            ErrorKind::Value,
+            "(float) Could not convert value to float",
+        )),
+    }
+}
+fn decimal(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(decimal) Not enough arguments!",
+        ));
+    }
+
+    match &args[0] {
+        // A float becomes the decimal it is shown as, so `decimal 0.1` is exactly `0.1d`.
+        Value::Float(float) if float.is_finite() => match float.to_string().parse() {
+            Ok(decimal) => Ok(Value::Decimal(decimal)),
+            Err(_) => Err(Error::new(
+                ErrorKind::Value,
+                "(decimal) Could not convert value to decimal",
+            )),
+        },
+        Value::Str(string) => match string.trim().parse::<BigDecimal>() {
+            Ok(decimal) => Ok(Value::Decimal(decimal)),
+            Err(_) => Err(Error::new(
+                ErrorKind::Value,
+                "(decimal) Could not convert value to decimal",
+            )),
+        },
+        value @ (Value::Int(_) | Value::BigInt(_) | Value::Decimal(_)) => {
+            Ok(Value::Decimal(decimal_arg(value, "decimal")?))
+        }
+        _ => Err(Error::new(
+            ErrorKind::Value,
+            "(decimal) Could not convert value to decimal",
+        )),
+    }
+}
+
+fn vars(_tokens: Vec<Token>, _meta: &mut Metadata) -> Result<Value, Error> {
+    let variables = get_variables();
+
+    for (k, v) in variables.variables() {
+        println!("{}: <{:?}>{}", k, v.ty(), v);
+    }
+
+    Ok(Value::None)
+}
+
+fn format_array(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(format_array) Not enough arguments!",
+        ));
+    }
+
+    let array = match &args[0] {
+        Value::Array(items) => items,
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(format_array) Expected an array but got type {:?}!",
+                    value.ty()
+                ),
             ))
         }
     };
-    let second = match tokens[1].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => tokens[1].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(*=) Invalid type: Cannot multiply thing of type {ty:?} with variable"
+
+    for item in array {
+        println!("{item}");
+    }
+
+    Ok(Value::None)
+}
+
+/// `get values index`: the item at `index` of an array, or the character at `index` of a
+/// string. `get map key` or `get map key default`: the value of `key` in a map, or `default` if
+/// there is no such key. `values[index]` and `map[key]` are the same.
+fn get(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(get) Not enough arguments!",
+        ));
+    }
+
+    match &args[0] {
+        Value::Array(items) => {
+            let i = index_arg(&args[1], items.len(), "get")?;
+
+            Ok(items[i].clone())
+        }
+        Value::Str(string) => {
+            let i = index_arg(&args[1], string.chars().count(), "get")?;
+
+            Ok(Value::Str(string.chars().nth(i).unwrap().to_string()))
+        }
+        Value::Map(entries) => match find_key(entries, &args[1]) {
+            Some(i) => Ok(entries[i].1.clone()),
+            None => match args.get(2) {
+                Some(default) => Ok(default.clone()),
+                None => Err(Error::new(
+                    ErrorKind::Key,
+                    format!("(get) Key {} not found!", args[1].repr()),
+                )),
+            },
+        },
+        value => Err(Error::new(
+            ErrorKind::Type,
+            format!("(get) Type {:?} cannot be indexed!", value.ty()),
+        )),
+    }
+}
+
+/// `set array index value` or `set map key value`: changes an item of the array or map held by
+/// a variable. `set name i j value` changes item `j` of item `i`, and so on. `name[index] = value`
+/// and `name[i][j] = value` are the same.
+fn set(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let name = match tokens.first() {
+        Some(token) if token.ty == TokenTypes::VARIABLE => token.val.to_string(),
+        _ => return Err(Error::new(ErrorKind::Type, "(set) Expected a variable!")),
+    };
+    let args = get_args(tokens[1..].to_vec(), meta, false)?;
+
+    let (value, path) = match args.split_last() {
+        Some((value, path)) if !path.is_empty() => (value, path),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Argument,
+                "(set) Not enough arguments!",
             ))
         }
     };
 
-    let mut variables = VARIABLES.write().unwrap();
+    let set = VARIABLES.write().unwrap().with_mut(&name, |collection| {
+        set_item(collection, path, value.clone())
+    });
 
-    let variable = variables.get_mut(first);
+    match set {
+        Some(set) => set?,
+        None => {
+            return Err(Error::new(
+                ErrorKind::Name,
+                format!("(set) Variable `{name}` not found"),
+            ))
+        }
+    }
+
+    Ok(Value::None)
+}
 
-    let variable = match variable {
-        Some(v) => v,
-        None => return Err(format!("(*=) Variable `{first}` not found")),
+/// Changes the item of `collection` at `path`, where every index after the first is an index of
+/// the item found by the one before it. A missing key is added to the map it is set in.
+fn set_item(collection: &mut Value, path: &[Value], value: Value) -> Result<(), Error> {
+    let (index, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            *collection = value;
+            return Ok(());
+        }
     };
 
-    let value: f64 = match &variable.value.ty {
-        TokenTypes::INT => variable.value.val.parse().unwrap(),
-        TokenTypes::FLOAT => variable.value.val.parse().unwrap(),
-        ty => {
-            return Err(format!(
-                "(*=) Invalid type: Cannot use operation '*=' with variable of type {ty:?}"
+    let item = match collection {
+        Value::Array(items) => {
+            let i = index_arg(index, items.len(), "set")?;
+
+            &mut items[i]
+        }
+        Value::Map(entries) => {
+            check_key(index, "set")?;
+
+            match find_key(entries, index) {
+                Some(i) => &mut entries[i].1,
+                None if rest.is_empty() => {
+                    entries.push((index.clone(), value));
+                    return Ok(());
+                }
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::Key,
+                        format!("(set) Key {} not found!", index.repr()),
+                    ))
+                }
+            }
+        }
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(set) Type {:?} has no items to set!", value.ty()),
             ))
         }
     };
 
-    let total = value * second;
+    set_item(item, rest, value)
+}
 
-    if total.fract() == 0.0 {
-        let total = total as i64;
+/// `remove array index` or `remove map key`: takes an item out of the array or map held by a
+/// variable, giving it.
+fn remove(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let name = held_name(&tokens, "remove")?;
+    let args = get_args(tokens[1..].to_vec(), meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(remove) Not enough arguments!",
+        ));
+    }
 
-        *variable = Variable {
-            value: Token {
-                ty: TokenTypes::INT,
-                modifiers: variable.value.modifiers.clone(),
-                val: total.to_string(),
-            },
-            scope: variable.scope,
-        };
+    change_held(&name, |collection| match collection {
+        Value::Array(items) => {
+            let i = index_arg(&args[0], items.len(), "remove")?;
+            Ok(items.remove(i))
+        }
+        Value::Map(entries) => match find_key(entries, &args[0]) {
+            Some(i) => Ok(entries.remove(i).1),
+            None => Err(Error::new(
+                ErrorKind::Key,
+                format!("(remove) Key {} not found!", args[0].repr()),
+            )),
+        },
+        value => Err(Error::new(
+            ErrorKind::Type,
+            format!("(remove) Type {:?} has no items to remove!", value.ty()),
+        )),
+    })
+}
+
+/// `slice values start end step`: the items of an array, or the characters of a string, from
+/// `start` up to (not including) `end`, taking every `step`th one. `values[start:end:step]` is
+/// the same. Any of the bounds can be `None` to go from the start or to the end, and negative
+/// ones count from the end. With a negative step, the items are taken backwards.
+fn slice(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(slice) Not enough arguments!",
+        ));
+    }
+
+    let items: Vec<Value> = match &args[0] {
+        Value::Array(items) => items.clone(),
+        Value::Str(string) => string.chars().map(|c| Value::Str(c.to_string())).collect(),
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(slice) Type {:?} cannot be sliced!", value.ty()),
+            ))
+        }
+    };
+
+    let len = items.len() as i64;
+    let bound = |i: usize| args.get(i).filter(|arg| **arg != Value::None);
+
+    let step = match bound(3) {
+        Some(step) => int_arg(step, "slice")?,
+        None => 1,
+    };
+
+    if step == 0 {
+        return Err(Error::new(
+            ErrorKind::Value,
+            "(slice) The step cannot be zero!",
+        ));
+    }
+
+    // A bound can be one past the last item, where a slice going forwards ends.
+    let place = |bound: &Value| -> Result<i64, Error> {
+        let i = int_arg(bound, "slice")?;
+        let place = if i < 0 { i + len } else { i };
+
+        if place < 0 || place > len {
+            return Err(Error::new(
+                ErrorKind::Index,
+                format!("(slice) Index {i} is out of range for {len} item(s)!"),
+            ));
+        }
+
+        Ok(place)
+    };
+
+    let (start, end) = if step > 0 {
+        let start = bound(1).map(place).transpose()?.unwrap_or(0);
+        let end = bound(2).map(place).transpose()?.unwrap_or(len);
+        (start, end)
     } else {
-        *variable = Variable {
-            value: Token {
-                ty: TokenTypes::FLOAT,
-                modifiers: variable.value.modifiers.clone(),
-                val: total.to_string(),
-            },
-            scope: variable.scope,
-        };
+        let start = bound(1).map(place).transpose()?.unwrap_or(len);
+        let end = bound(2).map(place).transpose()?.unwrap_or(-1);
+        // Going backwards, a slice starts at or before the last item.
+        (start.min(len - 1), end)
+    };
+
+    let mut sliced: Vec<Value> = vec![];
+    let mut i = start;
+
+    while (step > 0 && i < end) || (step < 0 && i > end) {
+        sliced.push(items[i as usize].clone());
+        i += step;
     }
 
-    return Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
-    });
+    Ok(match args[0] {
+        Value::Str(_) => Value::Str(sliced.iter().map(Value::to_string).collect()),
+        _ => Value::Array(sliced),
+    })
 }
 
-fn divide_assign(tokens: Vec<Token>, _meta: &mut Metadata) -> Result<Token, String> {
-    if tokens.len() < 2 {
-        return Err("(/=) Not enough arguments!".to_string());
+/// `push array value`: adds `value` to the end of the array held by a variable.
+fn push(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let name = held_name(&tokens, "push")?;
+    let args = get_args(tokens[1..].to_vec(), meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(push) Not enough arguments!",
+        ));
     }
 
-    let first = match tokens[0].ty.clone() {
-        TokenTypes::VARIABLE => &tokens[0].val,
-        ty => {
-            return Err(format!(
-                "(/=) Invalid type: Cannot use operation '/=' with type {ty:?}"
+    change_held_array(&name, "push", |items| {
+        items.extend(args);
+        Ok(Value::None)
+    })
+}
+
+/// `pop array`: takes the last item out of the array held by a variable, giving it.
+fn pop(tokens: Vec<Token>, _meta: &mut Metadata) -> Result<Value, Error> {
+    let name = held_name(&tokens, "pop")?;
+
+    change_held_array(&name, "pop", |items| match items.pop() {
+        Some(item) => Ok(item),
+        None => Err(Error::new(
+            ErrorKind::Value,
+            "(pop) Cannot pop from an empty array!",
+        )),
+    })
+}
+
+/// `insert array index value`: puts `value` at `index` of the array held by a variable, moving
+/// the items from `index` on one place up.
+fn insert(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let name = held_name(&tokens, "insert")?;
+    let mut args = get_args(tokens[1..].to_vec(), meta, false)?;
+
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(insert) Not enough arguments!",
+        ));
+    }
+
+    let item = args.swap_remove(1);
+
+    change_held_array(&name, "insert", |items| {
+        let i = index_arg(&args[0], items.len() + 1, "insert")?;
+
+        items.insert(i, item);
+        Ok(Value::None)
+    })
+}
+
+/// `contains array value`: whether `value` is one of the items of `array`.
+/// `contains string part`: whether `part` is found in `string`.
+fn contains(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(contains) Not enough arguments!",
+        ));
+    }
+
+    let found = match &args[0] {
+        Value::Array(items) => items.contains(&args[1]),
+        Value::Str(string) => string.contains(string_arg(&args[1], "contains")?),
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(contains) Cannot look inside type {:?}!", value.ty()),
             ))
         }
     };
-    let second = match tokens[1].ty.clone() {
-        TokenTypes::INT | TokenTypes::FLOAT => tokens[1].val.parse::<f64>().unwrap(),
-        ty => {
-            return Err(format!(
-                "(/=) Invalid type: Variable cannot be divided by thing of type {ty:?}"
+
+    Ok(Value::Bool(found))
+}
+
+/// `reverse array`: a copy of `array` with its items in the opposite order.
+fn reverse(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(reverse) Not enough arguments!",
+        ));
+    }
+
+    let mut items = match &args[0] {
+        Value::Array(items) => items.clone(),
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(reverse) Expected an array but got type {:?}!", value.ty()),
             ))
         }
     };
 
-    let mut variables = VARIABLES.write().unwrap();
+    items.reverse();
+
+    Ok(Value::Array(items))
+}
 
-    let variable = variables.get_mut(first);
+/// `sort array`: a copy of `array` with its items from smallest to largest. The items must be all
+/// numbers or all strings.
+fn sort(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-    let variable = match variable {
-        Some(v) => v,
-        None => return Err(format!("(/=) Variable `{first}` not found")),
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(sort) Not enough arguments!",
+        ));
+    }
+
+    let mut items = match &args[0] {
+        Value::Array(items) => items.clone(),
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(sort) Expected an array but got type {:?}!", value.ty()),
+            ))
+        }
     };
 
-    let value: f64 = match &variable.value.ty {
-        TokenTypes::INT => variable.value.val.parse().unwrap(),
-        TokenTypes::FLOAT => variable.value.val.parse().unwrap(),
-        ty => {
-            return Err(format!(
-                "(/=) Invalid type: Cannot use operation '/=' with variable of type {ty:?}"
+    if items.iter().all(|item| number_arg(item, "sort").is_ok()) {
+        items.sort_by(|a, b| compare(a, b, "sort").unwrap().unwrap_or(Ordering::Equal));
+    } else if items.iter().all(|item| matches!(item, Value::Str(_))) {
+        items.sort_by_key(Value::to_string);
+    } else {
+        return Err(Error::new(
+            ErrorKind::Type,
+            "(sort) Can only sort arrays of numbers or arrays of strings!",
+        ));
+    }
+
+    Ok(Value::Array(items))
+}
+
+/// `keys map`: an array of the keys of `map`.
+fn keys(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(keys) Not enough arguments!",
+        ));
+    }
+
+    let entries = map_arg(&args[0], "keys")?;
+
+    Ok(Value::Array(
+        entries.iter().map(|(key, _)| key.clone()).collect(),
+    ))
+}
+
+/// `values map`: an array of the values of `map`.
+fn values(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(values) Not enough arguments!",
+        ));
+    }
+
+    let entries = map_arg(&args[0], "values")?;
+
+    Ok(Value::Array(
+        entries.iter().map(|(_, value)| value.clone()).collect(),
+    ))
+}
+
+/// `has map key`: whether `map` has the key `key`.
+fn has(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(has) Not enough arguments!",
+        ));
+    }
+
+    let entries = map_arg(&args[0], "has")?;
+
+    Ok(Value::Bool(find_key(entries, &args[1]).is_some()))
+}
+
+/// `len value`: how many items an array has, how many keys a map has, or how many characters a
+/// string has.
+fn len(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(len) Not enough arguments!",
+        ));
+    }
+
+    let len = match &args[0] {
+        Value::Array(items) => items.len(),
+        Value::Map(entries) => entries.len(),
+        Value::Str(string) => string.chars().count(),
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(len) Type {:?} has no length!", value.ty()),
             ))
         }
     };
 
-    let total = value / second;
+    Ok(Value::Int(len as i64))
+}
 
-    if total.fract() == 0.0 {
-        let total = total as i64;
+/// `upper string`: a copy of `string` in upper case.
+fn upper(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-        *variable = Variable {
-            value: Token {
-                ty: TokenTypes::INT,
-                modifiers: variable.value.modifiers.clone(),
-                val: total.to_string(),
-            },
-            scope: variable.scope,
-        };
-    } else {
-        *variable = Variable {
-            value: Token {
-                ty: TokenTypes::FLOAT,
-                modifiers: variable.value.modifiers.clone(),
-                val: total.to_string(),
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(upper) Not enough arguments!",
+        ));
+    }
+
+    Ok(Value::Str(string_arg(&args[0], "upper")?.to_uppercase()))
+}
+
+/// `lower string`: a copy of `string` in lower case.
+fn lower(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(lower) Not enough arguments!",
+        ));
+    }
+
+    Ok(Value::Str(string_arg(&args[0], "lower")?.to_lowercase()))
+}
+
+/// `trim string`: a copy of `string` without the whitespace at its start and end.
+fn trim(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(trim) Not enough arguments!",
+        ));
+    }
+
+    Ok(Value::Str(string_arg(&args[0], "trim")?.trim().to_string()))
+}
+
+/// `split string separator`: an array of the parts of `string` between each `separator`.
+/// Without a separator, `string` is split on whitespace.
+fn split(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(split) Not enough arguments!",
+        ));
+    }
+
+    let string = string_arg(&args[0], "split")?;
+
+    let parts: Vec<&str> =
+        match args.get(1) {
+            Some(separator) => match string_arg(separator, "split")? {
+                "" => return Err(Error::new(
+                    ErrorKind::Value,
+                    "(split) The separator cannot be empty! Use `chars` to split into characters.",
+                )),
+                separator => string.split(separator).collect(),
             },
-            scope: variable.scope,
+            None => string.split_whitespace().collect(),
         };
+
+    Ok(Value::Array(
+        parts
+            .into_iter()
+            .map(|part| Value::Str(part.to_string()))
+            .collect(),
+    ))
+}
+
+/// `join array separator`: the strings of `array` joined into one, with `separator` (or nothing)
+/// between them.
+fn join(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(join) Not enough arguments!",
+        ));
     }
 
-    return Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
-    });
+    let items = match &args[0] {
+        Value::Array(items) => items,
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!("(join) Expected an array but got type {:?}!", value.ty()),
+            ))
+        }
+    };
+
+    let separator = match args.get(1) {
+        Some(separator) => string_arg(separator, "join")?,
+        None => "",
+    };
+
+    let parts = items
+        .iter()
+        .map(|item| string_arg(item, "join"))
+        .collect::<Result<Vec<&str>, Error>>()?;
+
+    Ok(Value::Str(parts.join(separator)))
 }
 
-fn break_(_tokens: Vec<Token>, _meta: &mut Metadata) -> Result<Token, String> {
-    return Ok(Token {
-        ty: TokenTypes::FUNC,
-        modifiers: vec![],
-        val: "break".to_string(),
-    });
+/// `replace string from to`: a copy of `string` with every `from` changed to `to`.
+fn replace_(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.len() < 3 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(replace) Not enough arguments!",
+        ));
+    }
+
+    let string = string_arg(&args[0], "replace")?;
+    let from = string_arg(&args[1], "replace")?;
+    let to = string_arg(&args[2], "replace")?;
+
+    if from.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Value,
+            "(replace) The text to replace cannot be empty!",
+        ));
+    }
+
+    Ok(Value::Str(string.replace(from, to)))
+}
+
+/// `starts_with string prefix`: whether `string` starts with `prefix`.
+fn starts_with(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(starts_with) Not enough arguments!",
+        ));
+    }
+
+    let string = string_arg(&args[0], "starts_with")?;
+
+    Ok(Value::Bool(
+        string.starts_with(string_arg(&args[1], "starts_with")?),
+    ))
 }
 
-fn int(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+/// `ends_with string suffix`: whether `string` ends with `suffix`.
+fn ends_with(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(ends_with) Not enough arguments!",
+        ));
+    }
+
+    let string = string_arg(&args[0], "ends_with")?;
+
+    Ok(Value::Bool(
+        string.ends_with(string_arg(&args[1], "ends_with")?),
+    ))
+}
 
-    let convertable = &args[0].val;
-    match convertable.parse::<i64>() {
-        Ok(int) => int,
-        Err(_) => return Err("(int) Could not convert value to integer".to_string()),
+/// `find string part`: the index of the first character of the first `part` in `string`, or `-1`
+/// if it is not there.
+fn find(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(find) Not enough arguments!",
+        ));
+    }
+
+    let string = string_arg(&args[0], "find")?;
+
+    // Indices count characters, like `len` and `string[i]`, not bytes.
+    let index = match string.find(string_arg(&args[1], "find")?) {
+        Some(byte) => string[..byte].chars().count() as i64,
+        None => -1,
     };
 
-    return Ok(Token {
-        ty: TokenTypes::INT,
-        modifiers: vec![],
-        val: convertable.to_string(),
-    });
+    Ok(Value::Int(index))
 }
-fn float(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
 
-    let convertable = &args[0].val;
-    match convertable.parse::<f64>() {
-        Ok(int) => int,
-        Err(_) => return Err("(float) Could not convert value to float".to_string()),
+/// The longest string that `repeat` makes, in bytes.
+const MAX_REPEAT_LEN: usize = 1 << 30;
+
+/// `repeat string times`: `string` written `times` times in a row.
+fn repeat(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(repeat) Not enough arguments!",
+        ));
+    }
+
+    let string = string_arg(&args[0], "repeat")?;
+
+    let times = match &args[1] {
+        Value::Int(times) if *times >= 0 => Some(*times as usize),
+        Value::BigInt(times) if times.sign() == Sign::Plus => None,
+        Value::Int(_) | Value::BigInt(_) => {
+            return Err(Error::new(
+                ErrorKind::Value,
+                format!("(repeat) Cannot repeat a string {} times!", args[1]),
+            ))
+        }
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(repeat) Expected an integer but got type {:?}!",
+                    value.ty()
+                ),
+            ))
+        }
     };
 
-    return Ok(Token {
-        ty: TokenTypes::FLOAT,
-        modifiers: vec![],
-        val: convertable.to_string(),
-    });
+    let len = times.and_then(|times| string.len().checked_mul(times));
+    let mut repeated = String::new();
+
+    match (times, len) {
+        (Some(_), Some(0)) => Ok(Value::Str(repeated)),
+        // A count that would not fit in memory is an error, not the end of the program.
+        (Some(times), Some(len))
+            if len <= MAX_REPEAT_LEN && repeated.try_reserve_exact(len).is_ok() =>
+        {
+            for _ in 0..times {
+                repeated.push_str(string);
+            }
+
+            Ok(Value::Str(repeated))
+        }
+        _ => Err(Error::new(
+            ErrorKind::Value,
+            format!(
+                "(repeat) Cannot repeat a string {} times, the count is too large!",
+                args[1]
+            ),
+        )),
+    }
 }
 
-fn vars(_tokens: Vec<Token>, _meta: &mut Metadata) -> Result<Token, String> {
-    let variables = VARIABLES.read().unwrap();
+/// `chars string`: an array of the characters of `string`, each as a string.
+fn chars(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
 
-    for (k, v) in variables.iter() {
-        println!("{}: <{:?}>{}", k, v.value.ty, v.value.val);
+    if args.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            "(chars) Not enough arguments!",
+        ));
     }
 
-    return Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
-    });
+    Ok(Value::Array(
+        string_arg(&args[0], "chars")?
+            .chars()
+            .map(|ch| Value::Str(ch.to_string()))
+            .collect(),
+    ))
 }
 
-fn format_array(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+/// Reads the string given to `fname`.
+fn string_arg<'a>(value: &'a Value, fname: &str) -> Result<&'a str, Error> {
+    match value {
+        Value::Str(string) => Ok(string),
+        value => Err(Error::new(
+            ErrorKind::Type,
+            format!("({fname}) Expected a string but got type {:?}!", value.ty()),
+        )),
+    }
+}
 
-    if args.len() < 1 {
-        return Err("(format_array) Not enough arguments!".to_string());
+/// Reads the map given to `fname`.
+fn map_arg<'a>(value: &'a Value, fname: &str) -> Result<&'a [(Value, Value)], Error> {
+    match value {
+        Value::Map(entries) => Ok(entries),
+        value => Err(Error::new(
+            ErrorKind::Type,
+            format!("({fname}) Expected a map but got type {:?}!", value.ty()),
+        )),
     }
+}
 
-    let array = read_array(&args[0], meta);
+/// Gives the name of the variable that is the first argument of `fname`, whose value it changes
+/// with [`change_held`].
+fn held_name(tokens: &[Token], fname: &str) -> Result<String, Error> {
+    match tokens.first() {
+        Some(token) if token.ty == TokenTypes::VARIABLE => Ok(token.val.to_string()),
+        _ => Err(Error::new(
+            ErrorKind::Type,
+            format!("({fname}) Expected a variable!"),
+        )),
+    }
+}
 
-    for token in array {
-        println!("{}", token.val);
+/// Runs `change` on the value of the variable `name` where it is kept, so nothing is copied. The
+/// other arguments have to be run before, as `change` runs while the variables are locked.
+fn change_held<T>(
+    name: &str,
+    change: impl FnOnce(&mut Value) -> Result<T, Error>,
+) -> Result<T, Error> {
+    match VARIABLES.write().unwrap().with_mut(name, change) {
+        Some(changed) => changed,
+        None => Err(Error::new(
+            ErrorKind::Name,
+            format!("Variable '{name}' does not exist!"),
+        )),
     }
+}
 
-    Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
+/// Like [`change_held`], for an array.
+fn change_held_array<T>(
+    name: &str,
+    fname: &str,
+    change: impl FnOnce(&mut Vec<Value>) -> Result<T, Error>,
+) -> Result<T, Error> {
+    change_held(name, |value| match value {
+        Value::Array(items) => change(items),
+        value => Err(Error::new(
+            ErrorKind::Type,
+            format!(
+                "({fname}) Expected an array but `{name}` has type {:?}!",
+                value.ty()
+            ),
+        )),
     })
 }
 
-fn quit(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, false);
+/// Reads `index` as the place of one of `len` items, for `fname`. Negative indices count from
+/// the end, so `-1` is the last item.
+fn index_arg(index: &Value, len: usize, fname: &str) -> Result<usize, Error> {
+    let i = int_arg(index, fname)?;
+    let place = if i < 0 { i + len as i64 } else { i };
+
+    if place < 0 || place >= len as i64 {
+        return Err(Error::new(
+            ErrorKind::Index,
+            format!("({fname}) Index {i} is out of range for {len} item(s)!"),
+        ));
+    }
 
-    let code: i32 = if args.len() > 0 {
-        match args[0].val.parse() {
-            Ok(n) => n,
-            Err(_) => {
-                return Err("(quit / exit) Error code is not a number".to_string());
-            }
+    Ok(place as usize)
+}
+
+/// Reads an integer given to `fname`.
+fn int_arg(value: &Value, fname: &str) -> Result<i64, Error> {
+    match value {
+        Value::Int(int) => Ok(*int),
+        Value::BigInt(int) => Err(Error::new(
+            ErrorKind::Index,
+            format!("({fname}) Index {int} is out of range!"),
+        )),
+        value => Err(Error::new(
+            ErrorKind::Type,
+            format!("({fname}) Indices must be integers, not {:?}!", value.ty()),
+        )),
+    }
+}
+
+/// Where `key` is among the keys and values of a map.
+fn find_key(entries: &[(Value, Value)], key: &Value) -> Option<usize> {
+    entries.iter().position(|(k, _)| k == key)
+}
+
+/// Checks that `key` can be a key of a map.
+fn check_key(key: &Value, fname: &str) -> Result<(), Error> {
+    match key {
+        key if key.is_number() => Ok(()),
+        Value::Str(_) | Value::Bool(_) => Ok(()),
+        key => Err(Error::new(
+            ErrorKind::Type,
+            format!(
+                "({fname}) Type {:?} cannot be a key, only strings, numbers and booleans can!",
+                key.ty()
+            ),
+        )),
+    }
+}
+
+fn quit(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    let code: i32 = match args.first() {
+        Some(Value::Int(n)) => *n as i32,
+        Some(_) => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                "(quit / exit) Error code is not a number",
+            ))
         }
-    } else {
-        0
+        None => 0,
     };
 
     exit(code);
 }
 
-fn func(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
-    let args = get_args(tokens, meta, true);
+/// `func name params... { body }`: defines a function. Like `fn`, it keeps copies of the
+/// variables of the scopes it is defined in.
+fn func(tokens: Vec<Token>, _meta: &mut Metadata) -> Result<Value, Error> {
+    let (name, mut custom_func) = define_func(&tokens)?;
+    let variables = get_variables();
 
-    let func_name = &args[0].val;
-    // let array = read_array(&args[1], meta);
-    let scopex = args[1].clone();
+    custom_func.captured = variables.capture();
+    custom_func.module = variables.module().to_string();
+    let key = variables.function_key(&name);
+    drop(variables);
 
     let custom_funcs = CUSTOM_FUNCS.write();
 
@@ -1397,11 +2708,174 @@ fn func(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Token, String> {
         }
     };
 
-    custom_funcs.insert(func_name.to_string(), scopex);
+    custom_funcs.insert(key, custom_func);
 
-    return Ok(Token {
-        ty: TokenTypes::NONE,
-        modifiers: vec![],
-        val: "None".to_string(),
-    });
+    Ok(Value::None)
+}
+
+/// `fn params... { body }`: makes a function without a name, to be stored in a variable or given
+/// to another function. It keeps copies of the variables of the scopes it is made in.
+fn fn_(tokens: Vec<Token>, _meta: &mut Metadata) -> Result<Value, Error> {
+    let mut function = read_func(&tokens, "fn")?;
+
+    let variables = get_variables();
+    function.captured = variables.capture();
+    function.module = variables.module().to_string();
+    drop(variables);
+
+    Ok(Value::Function(Function::Anonymous(Arc::new(function))))
+}
+
+/// `import "file.dwn"`, `import "file.dwn" as name` or `import names... from "file.dwn"`: runs
+/// another file as a module the first time it is imported. Its functions and global variables
+/// are then `name.thing` (`name` being the file name without `as`), or the names imported from it.
+fn import(tokens: Vec<Token>, meta: &mut Metadata) -> Result<Value, Error> {
+    let (path, import) = read_import(&tokens)?;
+
+    let file = match get_arg(path.clone(), meta, false)? {
+        Value::Str(file) => file,
+        value => {
+            return Err(Error::new(
+                ErrorKind::Type,
+                format!(
+                    "(import) Invalid type: Expected the path of a file, got {:?}",
+                    value.ty()
+                ),
+            ))
+        }
+    };
+
+    let module = find_module(&file, &path.span.file)?;
+
+    load_module(&module)?;
+    bind_module(&module, import)?;
+
+    Ok(Value::None)
+}
+
+/// Reads the arguments of `func name params... { body }` into the function's name and its
+/// [`CustomFunc`]. The names are taken as written, even if they are also variables.
+pub fn define_func(tokens: &[Token]) -> Result<(String, CustomFunc), Error> {
+    match tokens.split_first() {
+        Some((name, rest)) if name.ty != TokenTypes::SCOPE => {
+            Ok((name.val.to_string(), read_func(rest, "func")?))
+        }
+        _ => Err(Error::new(
+            ErrorKind::Syntax,
+            "(func) Expected a name, parameters and a scope!",
+        )),
+    }
+}
+
+/// Reads the `params... { body }` of a function made by `fname`.
+fn read_func(tokens: &[Token], fname: &str) -> Result<CustomFunc, Error> {
+    let (body, names) = match tokens.split_last() {
+        Some((body, names)) if body.ty == TokenTypes::SCOPE => (body, names),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Syntax,
+                format!("({fname}) Expected parameters and a scope!"),
+            ))
+        }
+    };
+
+    let mut params: Vec<String> = vec![];
+
+    for name in names {
+        if params.contains(&name.val) {
+            return Err(Error::new(
+                ErrorKind::Syntax,
+                format!("({fname}) Parameter `{}` is given twice!", name.val),
+            ));
+        }
+
+        params.push(name.val.to_string());
+    }
+
+    Ok(CustomFunc {
+        params,
+        body: body.clone(),
+        captured: vec![],
+        module: String::new(),
+    })
+}
+
+/// Starts a frame holding `locals`, for the scope about to be run by [`run_scope`]. A `call`
+/// frame is the start of a call of a function of the module it holds, which cannot see the
+/// variables of its caller.
+fn bind_locals(locals: Vec<(String, Value)>, call: Option<Call>) {
+    VARIABLES.write().unwrap().push(locals, call);
+}
+
+/// Drops the frame started by [`bind_locals`].
+fn unbind_locals() {
+    VARIABLES.write().unwrap().pop();
+}
+
+/// The function that a `CUSTOMFUNC` token named `name` calls: the `fn` held by the variable
+/// `name`, or else the function under the key `name` in [`CUSTOM_FUNCS`].
+pub fn find_custom_func(name: &str) -> Option<CustomFunc> {
+    if let Some(Some(Function::Anonymous(function))) = get_variables().with(name, Value::function) {
+        return Some(function.as_ref().clone());
+    }
+
+    match CUSTOM_FUNCS.read() {
+        Ok(custom_funcs) => custom_funcs.get(name).cloned(),
+        Err(e) => {
+            eprintln!("{e}");
+            exit(1);
+        }
+    }
+}
+
+/// How many function calls can run inside each other. The interpreter's thread has the stack
+/// for it.
+const MAX_CALL_DEPTH: usize = 1000;
+
+/// Calls a function defined with `func` or made with `fn`. Its arguments are bound to its
+/// parameters as variables that only exist during the call (hiding the variables the function
+/// keeps), and it gives the value of its `return` (or of its last line). The variables of the
+/// caller cannot be seen from inside the call, only the global ones of the function's module.
+pub fn call_custom_func(
+    name: &str,
+    func: &CustomFunc,
+    tokens: Vec<Token>,
+    meta: &mut Metadata,
+) -> Result<Value, Error> {
+    let args = get_args(tokens, meta, false)?;
+
+    if args.len() != func.params.len() {
+        return Err(Error::new(
+            ErrorKind::Argument,
+            format!(
+                "({name}) Expected {} argument(s) but got {}!",
+                func.params.len(),
+                args.len()
+            ),
+        ));
+    }
+
+    if *meta.call_depth >= MAX_CALL_DEPTH {
+        return Err(Error::new(
+            ErrorKind::Recursion,
+            format!("({name}) Functions can only call each other {MAX_CALL_DEPTH} deep!"),
+        ));
+    }
+
+    let call = Call {
+        module: func.module.to_string(),
+        captured: func.captured.clone(),
+    };
+    bind_locals(func.params.iter().cloned().zip(args).collect(), Some(call));
+    let loop_depth = replace(meta.loop_depth, 0);
+    *meta.call_depth += 1;
+    let flow = run_scope(&func.body, meta);
+    *meta.call_depth -= 1;
+    *meta.loop_depth = loop_depth;
+    unbind_locals();
+
+    match flow? {
+        Flow::Normal(ret) | Flow::Return(ret) => Ok(ret),
+        Flow::Break | Flow::Continue => unreachable!("`break` and `continue` need a loop"),
+    }
 }
@@ -0,0 +1,262 @@
+//! The variables of running Dawn (dwn) code, kept in a chain of frames.
+
+use std::{
+    collections::HashMap,
+    mem::{replace, take},
+    sync::{Arc, Mutex},
+};
+
+use crate::value::Value;
+
+/// The variables of one scope. A function made in the scope keeps them, so they live as long as
+/// the scope or the function, and changes made by either are seen by both.
+pub type Scope = Arc<Mutex<HashMap<String, Value>>>;
+
+/// A running scope.
+struct Frame {
+    variables: Scope,
+    /// The call of a function that starts with this frame, if it starts one.
+    call: Option<Call>,
+}
+
+/// The start of a call of a function. Code inside the call cannot see the frames of its caller,
+/// only the scopes the function keeps and the global variables of the function's module.
+pub struct Call {
+    pub module: String,
+    /// The scopes the function was made in, innermost first.
+    pub captured: Vec<Scope>,
+}
+
+/// Every variable that exists: the global variables of each module, and a frame for each scope
+/// that is running. The main file is the module `""`.
+///
+/// Examples:
+///
+/// ```rust
+/// let mut env = Environment::new();
+///
+/// env.declare("x".to_string(), Value::Int(1));
+/// env.push(vec![], None);
+/// env.declare("x".to_string(), Value::Int(2));
+///
+/// assert_eq!(env.get("x"), Some(Value::Int(2)));
+///
+/// env.pop();
+///
+/// assert_eq!(env.get("x"), Some(Value::Int(1)));
+/// ```
+pub struct Environment {
+    globals: HashMap<String, HashMap<String, Value>>,
+    /// The names that each module imported other modules as, like `u` in
+    /// `import "utils.dwn" as u`.
+    aliases: HashMap<String, HashMap<String, String>>,
+    frames: Vec<Frame>,
+    /// The module whose top level is running.
+    module: String,
+}
+
+/// What [`Environment::enter`] puts aside, for [`Environment::leave`] to put back.
+pub struct Outer {
+    frames: Vec<Frame>,
+    module: String,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment {
+            globals: HashMap::from([(String::new(), HashMap::new())]),
+            aliases: HashMap::new(),
+            frames: vec![],
+            module: String::new(),
+        }
+    }
+
+    /// Starts the frame of a scope, holding `locals`. A `call` frame is the start of a call of a
+    /// function.
+    pub fn push(&mut self, locals: Vec<(String, Value)>, call: Option<Call>) {
+        self.frames.push(Frame {
+            variables: Arc::new(Mutex::new(locals.into_iter().collect())),
+            call,
+        });
+    }
+
+    /// Drops the frame of the scope that ended, with its variables unless a function keeps them.
+    pub fn pop(&mut self) {
+        self.frames.pop();
+    }
+
+    /// Starts running the top level of `module`, putting the frames that are running aside.
+    pub fn enter(&mut self, module: String) -> Outer {
+        self.globals.entry(module.to_string()).or_default();
+
+        Outer {
+            frames: take(&mut self.frames),
+            module: replace(&mut self.module, module),
+        }
+    }
+
+    /// Goes back to the code that [`Environment::enter`] put aside.
+    pub fn leave(&mut self, outer: Outer) {
+        self.frames = outer.frames;
+        self.module = outer.module;
+    }
+
+    /// The module of the code that is running.
+    pub fn module(&self) -> &str {
+        self.frames
+            .iter()
+            .rev()
+            .find_map(|frame| frame.call.as_ref())
+            .map(|call| call.module.as_str())
+            .unwrap_or(&self.module)
+    }
+
+    /// The scopes that can be seen from the innermost frame, innermost first.
+    fn visible(&self) -> Vec<&Scope> {
+        let mut visible: Vec<&Scope> = vec![];
+
+        for frame in self.frames.iter().rev() {
+            visible.push(&frame.variables);
+
+            if let Some(call) = &frame.call {
+                visible.extend(&call.captured);
+                break;
+            }
+        }
+
+        visible
+    }
+
+    /// The module and name that `name` stands for. `alias.name` is a name of a module imported
+    /// as `alias`.
+    fn resolve<'a>(&'a self, name: &'a str) -> (&'a str, &'a str) {
+        let module = self.module();
+
+        if let Some((alias, rest)) = name.split_once('.') {
+            if let Some(target) = self
+                .aliases
+                .get(module)
+                .and_then(|aliases| aliases.get(alias))
+            {
+                return (target, rest);
+            }
+        }
+
+        (module, name)
+    }
+
+    /// A copy of the value of the nearest variable called `name`.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.with(name, Value::clone)
+    }
+
+    /// Gives `read` the value of the nearest variable called `name`, without copying it.
+    pub fn with<T>(&self, name: &str, read: impl FnOnce(&Value) -> T) -> Option<T> {
+        for scope in self.visible() {
+            if let Some(value) = scope.lock().unwrap().get(name) {
+                return Some(read(value));
+            }
+        }
+
+        let (module, name) = self.resolve(name);
+
+        self.globals.get(module)?.get(name).map(read)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.with(name, |_| ()).is_some()
+    }
+
+    /// Gives `change` the value of the nearest variable called `name`, to be changed where it is
+    /// kept.
+    pub fn with_mut<T>(&mut self, name: &str, change: impl FnOnce(&mut Value) -> T) -> Option<T> {
+        for scope in self.visible() {
+            if let Some(value) = scope.lock().unwrap().get_mut(name) {
+                return Some(change(value));
+            }
+        }
+
+        let (module, name) = self.resolve(name);
+        let (module, name) = (module.to_string(), name.to_string());
+
+        self.globals.get_mut(&module)?.get_mut(&name).map(change)
+    }
+
+    /// Creates a variable in the innermost frame (or as a global variable of the module at its
+    /// top level), hiding any variable with the same name around it. A variable of the same
+    /// frame is replaced.
+    pub fn declare(&mut self, name: String, value: Value) {
+        match self.frames.last_mut() {
+            Some(frame) => {
+                frame.variables.lock().unwrap().insert(name, value);
+            }
+            None => {
+                let module = self.module.to_string();
+
+                self.globals.entry(module).or_default().insert(name, value);
+            }
+        }
+    }
+
+    /// A global variable of `module`.
+    pub fn global(&self, module: &str, name: &str) -> Option<&Value> {
+        self.globals.get(module)?.get(name)
+    }
+
+    /// Makes `alias.name` stand for `name` of `module`, in the module that is running.
+    pub fn alias(&mut self, alias: String, module: String) {
+        let importer = self.module().to_string();
+
+        self.aliases
+            .entry(importer)
+            .or_default()
+            .insert(alias, module);
+    }
+
+    /// The key in `CUSTOM_FUNCS` of the function that `name` stands for in the running module.
+    pub fn function_key(&self, name: &str) -> String {
+        let (module, name) = self.resolve(name);
+
+        qualify(module, name)
+    }
+
+    /// Copies of the variables that can be seen, without the ones hidden by others of the same
+    /// name.
+    pub fn variables(&self) -> Vec<(String, Value)> {
+        let mut variables: Vec<(String, Value)> = vec![];
+        let mut add = |name: &String, value: &Value| {
+            if !variables.iter().any(|(seen, _)| seen == name) {
+                variables.push((name.to_string(), value.clone()));
+            }
+        };
+
+        for scope in self.visible() {
+            for (name, value) in scope.lock().unwrap().iter() {
+                add(name, value);
+            }
+        }
+
+        if let Some(globals) = self.globals.get(self.module()) {
+            for (name, value) in globals {
+                add(name, value);
+            }
+        }
+
+        variables
+    }
+
+    /// The scopes that can be seen and are not global, for a function made here to keep.
+    pub fn capture(&self) -> Vec<Scope> {
+        self.visible().into_iter().cloned().collect()
+    }
+}
+
+/// The key in `CUSTOM_FUNCS` of the function `name` of `module`. The functions of the main file
+/// keep their names.
+pub fn qualify(module: &str, name: &str) -> String {
+    if module.is_empty() {
+        name.to_string()
+    } else {
+        format!("{module}::{name}")
+    }
+}
@@ -0,0 +1,101 @@
+//! The errors that running Dawn (dwn) code can raise, which `try` can catch.
+
+use std::fmt::{self, Display};
+
+use crate::lexer::Span;
+
+/// What kind of thing went wrong. Dawn code sees it as the `kind` of a caught error.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ErrorKind {
+    /// A function was given too few or too many arguments.
+    Argument,
+    /// A variable, name or function does not exist.
+    Name,
+    /// A value has the wrong type for what is done with it.
+    Type,
+    /// A value has the right type but cannot be used, like `int "abc"`.
+    Value,
+    /// An index is out of range.
+    Index,
+    /// A key is not in a map.
+    Key,
+    /// A number was divided by zero.
+    ZeroDivision,
+    /// A statement is not written the way it has to be, like `break` outside a loop.
+    Syntax,
+    /// Reading or writing failed.
+    Io,
+    /// A module could not be found or imported.
+    Import,
+    /// Functions called each other too deeply, like a function that never stops calling itself.
+    Recursion,
+    /// An error made by `raise`, with the kind it was given.
+    Custom(String),
+}
+
+/// Shows the kind the way Dawn code sees it, like `TypeError`.
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Argument => write!(f, "ArgumentError"),
+            ErrorKind::Name => write!(f, "NameError"),
+            ErrorKind::Type => write!(f, "TypeError"),
+            ErrorKind::Value => write!(f, "ValueError"),
+            ErrorKind::Index => write!(f, "IndexError"),
+            ErrorKind::Key => write!(f, "KeyError"),
+            ErrorKind::ZeroDivision => write!(f, "ZeroDivisionError"),
+            ErrorKind::Syntax => write!(f, "SyntaxError"),
+            ErrorKind::Io => write!(f, "IOError"),
+            ErrorKind::Import => write!(f, "ImportError"),
+            ErrorKind::Recursion => write!(f, "RecursionError"),
+            ErrorKind::Custom(kind) => write!(f, "{kind}"),
+        }
+    }
+}
+
+/// An error raised while running Dawn code. It goes up through the functions and scopes that are
+/// running until a `try` catches it, or until it stops the program.
+///
+/// Examples:
+///
+/// ```rust
+/// let error = Error::new(ErrorKind::Value, "(int) Could not convert value to integer");
+///
+/// assert_eq!(error.kind.to_string(), "ValueError".to_string());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Where the error was raised. Set by the first token that fails with it.
+    pub span: Option<Span>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Error {
+        Error {
+            kind,
+            message: message.into(),
+            span: None,
+        }
+    }
+
+    /// Gives the error the place it was raised at, unless it already has one.
+    pub fn at(mut self, span: &Span) -> Error {
+        if self.span.is_none() {
+            self.span = Some(span.clone());
+        }
+
+        self
+    }
+}
+
+/// Shows the error the way it is reported when nothing catches it.
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => write!(f, "Error at {}: {}", span, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
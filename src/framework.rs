@@ -30,12 +30,12 @@ pub fn make_framework() {
     text.push('\n');
 
     text.push_str("vars:\n");
-    for var in VARIABLES
+    for (var, _) in VARIABLES
         .read()
         .expect("Error: Could not access functions!")
-        .keys()
+        .variables()
     {
-        text.push_str(var);
+        text.push_str(&var);
         text.push('\n');
     }
 
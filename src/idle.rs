@@ -7,6 +7,7 @@ use std::{
 
 use crate::{
     dwn::{get_funcs, Metadata},
+    lexer::{strip_comments, LineState, Span},
     runner::run,
 };
 
@@ -14,14 +15,22 @@ use crate::{
 pub fn idle() {
     let mut count: usize = 1;
     let mut scope = 0;
-    let mut in_scope = false;
+    let mut scope_depth = 0;
     let mut scope_token = String::new();
     let mut current_tokens = vec![];
+    let mut pending = String::new();
+    let mut line_state = LineState::default();
+    let start = Span::start_of("<idle>");
 
     loop {
         let mut code = String::new();
 
-        print!("> ");
+        // Show that the line continues a block or an unclosed statement.
+        if scope_depth > 0 || !pending.is_empty() {
+            print!("... ");
+        } else {
+            print!("> ");
+        }
         match stdout().flush() {
             Ok(_) => {}
             Err(e) => {
@@ -38,21 +47,28 @@ pub fn idle() {
             }
         }
 
-        let code = code.trim_end();
+        let code = strip_comments(code.trim_end_matches(['\n', '\r']), &mut line_state);
 
         if code.to_lowercase() == "quit" {
             break;
         }
 
         run(
-            code.to_string(),
+            code,
             get_funcs(),
             &mut Metadata {
-                line_count: count,
+                span: Span {
+                    line: count,
+                    ..start.clone()
+                },
                 scope: &mut scope,
-                in_scope: &mut in_scope,
+                scope_depth: &mut scope_depth,
                 scope_token: &mut scope_token,
                 current_tokens: &mut current_tokens,
+                pending: &mut pending,
+                flow: &mut None,
+                loop_depth: &mut 0,
+                call_depth: &mut 0,
             },
         );
 
@@ -2,52 +2,75 @@
 
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::process::exit;
 
 use crate::dwn::{get_funcs, Metadata};
-use crate::runner::run;
+use crate::error::{Error, ErrorKind};
+use crate::lexer::{check_unclosed, strip_comments, tokenize, LineState, Span};
+use crate::module::{finish_loading, module_path, start_loading};
+use crate::runner::run_tokens;
 
 /// The function used to interpret files.
 pub fn interpret_file(file: String) {
+    if let Err(err) = run_file(&file) {
+        eprintln!("{err}");
+        exit(1);
+    }
+}
+
+/// Runs the main file. It counts as a module being imported while it runs, so a module that
+/// imports it back is a circular import instead of running it again.
+pub fn run_file(file: &str) -> Result<(), Error> {
     let reader =
-        BufReader::new(File::open(&file).expect(format!("Cannot open file `{}`", file).as_str()));
+        BufReader::new(File::open(file).unwrap_or_else(|_| panic!("Cannot open file `{}`", file)));
 
+    let path = module_path(file);
+
+    start_loading(&path)?;
+    let ran = interpret(reader, file);
+    finish_loading(&path, ran.is_ok());
+
+    ran
+}
+
+/// Runs the lines of `file`, giving back the first error that nothing catches.
+pub fn interpret(reader: impl BufRead, file: &str) -> Result<(), Error> {
     let mut scope = 0;
-    let mut in_scope = false;
+    let mut scope_depth = 0;
     let mut scope_token = String::new();
     let mut current_tokens = vec![];
+    let mut pending = String::new();
+    let mut line_state = LineState::default();
+    let start = Span::start_of(file);
+    let mut last_line = 0;
 
     for (count, line) in reader.lines().enumerate() {
-        let line = remove_all_after(line.unwrap(), ';');
-
-        run(
-            line.trim_end().to_string(),
-            get_funcs(),
-            &mut Metadata {
-                line_count: count,
-                scope: &mut scope,
-                in_scope: &mut in_scope,
-                scope_token: &mut scope_token,
-                current_tokens: &mut current_tokens,
+        let line = line.map_err(|e| {
+            Error::new(ErrorKind::Io, format!("Cannot read `{file}`: {e}")).at(&start)
+        })?;
+        let line = strip_comments(&line, &mut line_state);
+        last_line = count + 1;
+
+        let mut meta = Metadata {
+            span: Span {
+                line: count + 1,
+                ..start.clone()
             },
-        );
+            scope: &mut scope,
+            scope_depth: &mut scope_depth,
+            scope_token: &mut scope_token,
+            current_tokens: &mut current_tokens,
+            pending: &mut pending,
+            flow: &mut None,
+            loop_depth: &mut 0,
+            call_depth: &mut 0,
+        };
+
+        let tokens = tokenize(line, &mut meta);
+        run_tokens(tokens, get_funcs(), &mut meta, true)?;
     }
-}
 
-/// The function to remove every character in `text` after `ch` is reached (including `ch`).
-///
-/// Examples:
-///
-/// ```rust
-/// let new = remove_all_after("say \"Hello!\" ; abcdefghij...".to_string(), ';');
-///
-/// assert_eq!(new, "say \"Hello!\" ".to_string());
-/// ```
-fn remove_all_after(text: String, ch: char) -> String {
-    text.split(ch).next().unwrap().to_string()
-}
+    check_unclosed(&pending, scope_depth, &scope_token, &start, last_line);
 
-#[test]
-fn removing_all_after() {
-    let new = remove_all_after("say \"Hello!\" ; abcdefghij...".to_string(), ';');
-    assert_eq!(new, "say \"Hello!\" ".to_string());
+    Ok(())
 }
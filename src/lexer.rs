@@ -1,10 +1,20 @@
 //! The lexer for Dawn (dwn)
 
+use std::fmt::{self, Display};
+use std::mem;
+use std::ops::Range;
 use std::process::exit;
+use std::sync::Arc;
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
 
 use crate::dwn::{get_funcs, Metadata, CUSTOM_FUNCS, VARIABLES};
+use crate::env::Environment;
+use crate::value::{Function, Value};
 
 /// The token types.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Debug, PartialEq)]
 pub enum TokenTypes {
     VARIABLE,
@@ -19,22 +29,207 @@ pub enum TokenTypes {
     NONE,
     BOOL,
     ARRAY,
+    TEMPLATE,
+    /// A function used as a value. Holds its key in `CUSTOM_FUNCS`.
+    FUNCTION,
+    MAP,
+    /// An exact decimal number, written with a `d` after it: `19.99d`.
+    DECIMAL,
 }
 
 /// The token modifiers.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Debug, Clone)]
 pub enum TokenModifiers {
     ARGS,
 }
 
+/// A position in a source file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub file: Arc<str>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// The span of the first character of `file`.
+    pub fn start_of(file: &str) -> Span {
+        Span {
+            file: Arc::from(file),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// The span just after `text`, when `text` starts at this span.
+    pub fn after(&self, text: &str) -> Span {
+        let mut span = self.clone();
+
+        for c in text.chars() {
+            if c == '\n' {
+                span.line += 1;
+                span.column = 1;
+            } else {
+                span.column += 1;
+            }
+        }
+
+        span
+    }
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Span::start_of("<unknown>")
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
 /// The token struct.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub ty: TokenTypes,
     pub modifiers: Vec<TokenModifiers>,
     pub val: String,
+    pub span: Span,
+}
+
+/// A piece of an interpolated string, as split up by [`template_parts`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplatePart {
+    /// Plain text, with its escape sequences already replaced.
+    Text(String),
+    /// The code inside a `{...}` placeholder, along with where it starts.
+    Placeholder(String, Span),
+}
+
+/// A piece of source text found by [`scan`], before it is classified into a [`Token`].
+#[derive(Debug, Clone, PartialEq)]
+enum Lexeme {
+    /// A name, keyword, number or operator.
+    Word(String),
+    /// The contents of a `"..."` string, exactly as written.
+    Str(String),
+    /// The contents of a `"..."` string with `{...}` placeholders, before escapes are replaced.
+    Template(String),
+    /// The contents of a `( ... )` literal.
+    Literal(String),
+    /// The items of a `[ ... ]` array.
+    Array(Vec<String>),
+    /// The `key: value` entries of a `#{key: value, ...}` map.
+    Map(Vec<String>),
+    /// A `name[index]` access. For `name[i][j]`, the name is the code that reads `name[i]`.
+    Index(String, String),
+    /// A `name[start:end:step]` slice, with the bounds that were given. Any of them can be empty.
+    Slice(String, Vec<String>),
+    /// The `{` that opens a scope continuing on the next lines.
+    Scope,
+    /// The contents of a `{ ... }` scope that is closed on the same line.
+    Block(String),
+}
+
+/// A lexeme, where it starts and the characters it covers in its line.
+type Scanned = (Lexeme, Span, Range<usize>);
+
+/// An expression over the lexemes of a line, referring to them by index.
+#[derive(Debug, PartialEq)]
+enum Expr {
+    /// A single value.
+    Atom(usize),
+    /// A `-` or `not` (at the index) applied to a value.
+    Prefix(usize, Box<Expr>),
+    /// An infix operator (at the index) applied to two values.
+    Binary(usize, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// The indices of the first and last lexemes of the expression.
+    fn bounds(&self) -> (usize, usize) {
+        match self {
+            Expr::Atom(i) => (*i, *i),
+            Expr::Prefix(op, right) => (*op, right.bounds().1),
+            Expr::Binary(_, left, right) => (left.bounds().0, right.bounds().1),
+        }
+    }
+}
+
+/// What an infix operator needs on its left-hand side.
+enum Operand {
+    Value,
+    Number,
+    /// A number, or a string to join another to.
+    Addable,
+    Variable,
+}
+
+impl Operand {
+    fn missing(&self, op: &str) -> String {
+        match self {
+            Operand::Value => format!("No first value for comparison operator '{op}' !"),
+            Operand::Number => format!("No first number for operator '{op}' !"),
+            Operand::Addable => format!("No first number or string for operator '{op}' !"),
+            Operand::Variable => format!("No variable for operator '{op}' !"),
+        }
+    }
 }
 
+/// The characters that operators are made of.
+const OPERATOR_CHARS: [char; 9] = ['+', '-', '*', '/', '%', '=', '!', '<', '>'];
+
+/// The operators recognized by the scanner, longest first.
+const OPERATORS: [&str; 17] = [
+    "==", "!=", ">=", "<=", "+=", "-=", "*=", "/=", "//", "+", "-", "*", "/", "%", "=", ">", "<",
+];
+
+/// An infix operator, the function it is rewritten into, what it needs on its left and how
+/// tightly it binds.
+type InfixOperator = (&'static str, &'static str, Operand, u8);
+
+/// How tightly each kind of operator binds, from loosest to tightest.
+const ASSIGNMENT: u8 = 1;
+const OR: u8 = 2;
+const AND: u8 = 3;
+const NOT: u8 = 4;
+const COMPARISON: u8 = 5;
+const TERM: u8 = 6;
+const FACTOR: u8 = 7;
+const NEGATION: u8 = 8;
+
+/// The infix operators.
+const INFIX_OPERATORS: [InfixOperator; 20] = [
+    ("or", "logical_or", Operand::Value, OR),
+    ("and", "logical_and", Operand::Value, AND),
+    ("==", "eq", Operand::Value, COMPARISON),
+    ("!=", "ne", Operand::Value, COMPARISON),
+    ("lazy=", "lazy_eq", Operand::Value, COMPARISON),
+    ("lazy!=", "lazy_ne", Operand::Value, COMPARISON),
+    (">", "gt", Operand::Value, COMPARISON),
+    ("<", "lt", Operand::Value, COMPARISON),
+    (">=", "ge", Operand::Value, COMPARISON),
+    ("<=", "le", Operand::Value, COMPARISON),
+    ("+", "sum", Operand::Addable, TERM),
+    ("-", "difference", Operand::Number, TERM),
+    ("*", "product", Operand::Number, FACTOR),
+    ("/", "quotient", Operand::Number, FACTOR),
+    ("//", "int_quotient", Operand::Number, FACTOR),
+    ("%", "remainder", Operand::Number, FACTOR),
+    ("+=", "add_assign", Operand::Variable, ASSIGNMENT),
+    ("-=", "subtract_assign", Operand::Variable, ASSIGNMENT),
+    ("*=", "multiply_assign", Operand::Variable, ASSIGNMENT),
+    ("/=", "divide_assign", Operand::Variable, ASSIGNMENT),
+];
+
+/// The prefix operators, the functions they are rewritten into, and how much of what follows
+/// they apply to. `-x` is rewritten into `difference 0 x`.
+const PREFIX_OPERATORS: [(&str, &str, u8); 2] =
+    [("-", "difference", NEGATION), ("not", "logical_not", NOT)];
+
 /// The tokenizer function.
 ///
 /// Examples:
@@ -63,216 +258,232 @@ pub struct Token {
 /// )
 /// ```
 pub fn tokenize(data: String, meta: &mut Metadata) -> Vec<Token> {
-    let mut tokens: Vec<Token> = vec![];
-    let mut in_func = false;
-    let mut in_string = false;
-    let mut string_token = String::new();
-    let mut in_variable_set = false;
-    let mut in_operator = false;
-    let mut in_compare = false;
-
-    let mut literal = String::new();
-    let mut in_literal = false;
+    let data = match continue_statement(data, meta) {
+        Some(data) => data,
+        None => return vec![],
+    };
+
+    if *meta.scope_depth > 0 {
+        let scanner = Scanner::new(&data, &meta.span);
+
+        let end = match scanner.block_end(meta.scope_depth) {
+            Some(end) => end,
+            None => {
+                store_scope_line(&data, meta);
+                return vec![];
+            }
+        };
 
-    let mut array = String::new();
-    let mut in_array = false;
+        let span = Span {
+            line: meta.span.line - meta.scope_token.lines().count(),
+            ..meta.span.clone()
+        };
 
-    let functions = get_funcs();
-    let variables = VARIABLES.read().unwrap();
-    let custom_funcs = CUSTOM_FUNCS.read().unwrap();
+        let before = scanner.text(0, end);
 
-    if data.is_empty() {
-        return vec![];
-    }
+        if !before.trim().is_empty() {
+            store_scope_line(&before, meta);
+        }
 
-    if *meta.in_scope {
-        if data.starts_with('}') {
-            *meta.in_scope = false;
-            let scope_token = meta.scope_token.to_string();
-            meta.scope_token.clear();
+        let scope_token = meta.scope_token.to_string();
+        meta.scope_token.clear();
 
-            let mut tokens: Vec<Token> = vec![];
-            for t in meta.current_tokens.clone() {
-                tokens.push(t);
-            }
+        let mut tokens: Vec<Token> = vec![];
+        for t in meta.current_tokens.clone() {
+            tokens.push(t);
+        }
 
-            match meta.current_tokens[0].ty {
-                TokenTypes::FUNC => tokens.push(Token {
-                    ty: TokenTypes::SCOPE,
-                    modifiers: vec![TokenModifiers::ARGS],
-                    val: scope_token,
-                }),
-                _ => {
-                    eprintln!(
-                        "Error on line {}: No function found to run scope!",
-                        meta.line_count + 1
-                    );
-                    exit(1);
-                }
+        match meta.current_tokens[0].ty {
+            TokenTypes::FUNC => tokens.push(Token {
+                ty: TokenTypes::SCOPE,
+                modifiers: vec![TokenModifiers::ARGS],
+                val: scope_token,
+                span,
+            }),
+            _ => {
+                eprintln!("Error at {}: No function found to run scope!", meta.span);
+                exit(1);
             }
-            return tokens;
-        } else {
-            let data = match data.strip_prefix("\t") {
-                Some(l) => l,
-                None => {
-                    eprintln!(
-                        "Error on line {}: Expected indent with tabs!",
-                        meta.line_count + 1
-                    );
-                    exit(1);
-                }
-            };
-            meta.scope_token.push_str(data);
-            meta.scope_token.push('\n');
-            return vec![];
         }
+
+        // Code after the `}`, like `else { ...`, goes on with the same statement.
+        let rest = scanner.text(end + 1, scanner.chars.len());
+        return tokenize_statement(&rest, &scanner.span(end + 1), tokens, meta);
     }
 
-    for raw_word in data.split(' ') {
-        let word1 = if raw_word.starts_with('(') && !in_string {
-            in_literal = true;
-            &raw_word[1..]
-        } else {
-            raw_word
-        };
+    tokenize_statement(&data, &meta.span.clone(), vec![], meta)
+}
 
-        let word = if word1.starts_with('[') && !in_string {
-            in_array = true;
-            &word1[1..]
-        } else {
-            word1
-        };
+/// Tokenizes the code of a statement that starts at `start`, adding to the `tokens` that the
+/// statement already has.
+fn tokenize_statement(
+    data: &str,
+    start: &Span,
+    mut tokens: Vec<Token>,
+    meta: &mut Metadata,
+) -> Vec<Token> {
+    let functions = get_funcs();
+    let variables = VARIABLES.read().unwrap();
+    let custom_funcs = CUSTOM_FUNCS.read().unwrap();
 
-        if in_literal {
-            if raw_word.ends_with(')') {
-                if in_string {
-                    if raw_word.strip_suffix(')').unwrap().ends_with('"') {
-                        in_string = false;
-                    }
+    if data.trim().is_empty() {
+        return tokens;
+    }
+
+    let scanner = Scanner::new(data, start);
+    let lexemes = scan(&scanner);
+
+    // Function names, `let` and `=` start a statement rather than a value, so they are never
+    // operands of an expression.
+    let starts_statement = |lexeme: &Lexeme| match lexeme {
+        Lexeme::Word(word) => {
+            match variables.with(word, |value| matches!(value, Value::Function(_))) {
+                Some(is_function) => is_function,
+                None => {
+                    word == "let"
+                        || word == "="
+                        || functions.contains_key(word.as_str())
+                        || custom_funcs.contains_key(&variables.function_key(word))
                 }
             }
+        }
+        Lexeme::Scope | Lexeme::Block(_) => true,
+        _ => false,
+    };
 
-            if !raw_word.starts_with('(') {
-                literal.push(' ');
-            }
+    let mut exprs: Vec<Expr> = vec![];
+    let mut pos = 0;
 
-            if raw_word.ends_with(')') && !in_string {
-                in_literal = false;
-                literal.push_str(&word[..word.len() - 1]);
+    // `name[index] = value` sets an item of an array or map, and `name[i][j] = value` sets an
+    // item of one of its items.
+    if let [(Lexeme::Index(..), span, range), (Lexeme::Word(eq), ..), ..] = &lexemes[..] {
+        if eq == "=" && tokens.is_empty() {
+            let (name, indices) = scanner.index_chain(range.start, range.end);
 
+            tokens.push(Token {
+                ty: TokenTypes::FUNC,
+                modifiers: vec![],
+                val: "set".to_string(),
+                span: span.clone(),
+            });
+            tokens.extend(classify(
+                Lexeme::Word(name),
+                span.clone(),
+                vec![TokenModifiers::ARGS],
+                &variables,
+            ));
+
+            for (index, at) in indices {
                 tokens.push(Token {
                     ty: TokenTypes::LITERAL,
-                    modifiers: if in_func || in_compare {
-                        vec![TokenModifiers::ARGS]
-                    } else {
-                        vec![]
-                    },
-                    val: literal.clone(),
+                    modifiers: vec![TokenModifiers::ARGS],
+                    val: index,
+                    span: scanner.span(at),
                 });
-
-                literal.clear();
-
-                continue;
-            } else {
-                literal.push_str(word);
-                continue;
             }
-        }
 
-        if in_array {
-            if word1.ends_with(']') {
-                if in_string {
-                    if word1.strip_suffix(']').unwrap().ends_with('"') {
-                        in_string = false;
-                    }
-                }
-            }
+            pos = 2;
+        }
+    }
 
-            if !word1.starts_with('[') {
-                array.push(' ');
-            }
+    // `name = value` changes the nearest variable called `name`.
+    if let [(Lexeme::Word(name), span, _), (Lexeme::Word(eq), ..), ..] = &lexemes[..] {
+        if eq == "=" && tokens.is_empty() {
+            tokens.push(Token {
+                ty: TokenTypes::FUNC,
+                modifiers: vec![],
+                val: "assign".to_string(),
+                span: span.clone(),
+            });
+            tokens.push(Token {
+                ty: TokenTypes::STRING,
+                modifiers: vec![TokenModifiers::ARGS],
+                val: name.to_string(),
+                span: span.clone(),
+            });
 
-            if !in_string {
-                if word1.ends_with(']') {
-                    in_array = false;
-                    array.push_str(&word[..word.len() - 1]);
+            pos = 2;
+        }
+    }
 
-                    tokens.push(Token {
-                        ty: TokenTypes::ARRAY,
-                        modifiers: if in_func || in_compare {
-                            vec![TokenModifiers::ARGS]
-                        } else {
-                            vec![]
-                        },
-                        val: array.clone(),
-                    });
+    while pos < lexemes.len() {
+        if starts_statement(&lexemes[pos].0) {
+            exprs.push(Expr::Atom(pos));
+            pos += 1;
+        } else {
+            exprs.push(parse_expr(&lexemes, &mut pos, 0));
+        }
+    }
 
-                    array.clear();
+    let mut in_func = !tokens.is_empty();
+    let mut in_variable_set = false;
+    let mut in_operator = false;
 
-                    continue;
-                } else if word.ends_with(',') && !in_string && !word.starts_with('"') {
-                    array.push_str(&word[..word.len() - 1]);
-                    array.push('\x05');
-                } else {
-                    array.push_str(word);
-                }
+    for expr in exprs {
+        let modifiers = if in_func || in_operator {
+            vec![TokenModifiers::ARGS]
+        } else {
+            vec![]
+        };
 
+        let (lexeme, span) = match expr {
+            Expr::Atom(i) => (lexemes[i].0.clone(), lexemes[i].1.clone()),
+            expr if tokens.is_empty() => {
+                tokens.extend(call_operator(&expr, &lexemes, &scanner, &variables));
+                in_operator = true;
                 continue;
-            } else {
-                array.push_str(word);
+            }
+            expr => {
+                tokens.push(expr_literal(&expr, &lexemes, &scanner, modifiers));
                 continue;
             }
-        }
+        };
 
-        if word == "None" && !in_string {
-            if !in_literal {
-                tokens.push(Token {
-                    ty: TokenTypes::NONE,
-                    modifiers: if in_func || in_compare {
-                        vec![TokenModifiers::ARGS]
-                    } else {
-                        vec![]
-                    },
-                    val: "None".to_string(),
-                });
+        let word = match lexeme {
+            Lexeme::Scope => {
+                let rest = scanner.text(lexemes[lexemes.len() - 1].2.end, scanner.chars.len());
+
+                if !rest.trim().is_empty() {
+                    eprintln!(
+                        "Error at {}: Unexpected code after '{{' ! Close the block on this line or move the code to the next one.",
+                        span
+                    );
+                    exit(1);
+                }
+
+                *meta.scope_depth = 1;
+                *meta.current_tokens = tokens.clone();
+
+                return vec![];
             }
-            continue;
-        }
-        if word == "true" && !in_string {
-            if !in_literal {
+            Lexeme::Block(block) => {
+                if tokens.is_empty() {
+                    eprintln!("Error at {}: No function found to run scope!", span);
+                    exit(1);
+                }
+
                 tokens.push(Token {
-                    ty: TokenTypes::BOOL,
-                    modifiers: if in_func || in_compare {
-                        vec![TokenModifiers::ARGS]
-                    } else {
-                        vec![]
-                    },
-                    val: "true".to_string(),
+                    ty: TokenTypes::SCOPE,
+                    modifiers: vec![TokenModifiers::ARGS],
+                    val: block,
+                    span,
                 });
+                continue;
             }
-            continue;
-        }
-        if word == "false" && !in_string {
-            if !in_literal {
-                tokens.push(Token {
-                    ty: TokenTypes::BOOL,
-                    modifiers: if in_func || in_compare {
-                        vec![TokenModifiers::ARGS]
-                    } else {
-                        vec![]
-                    },
-                    val: "false".to_string(),
-                });
+            Lexeme::Word(word) => word,
+            lexeme => {
+                tokens.extend(classify(lexeme, span, modifiers, &variables));
+                continue;
             }
-            continue;
-        }
+        };
 
-        if word == "let" && !in_string {
+        if word == "let" {
             in_variable_set = true;
             tokens.push(Token {
                 ty: TokenTypes::FUNC,
                 modifiers: vec![],
                 val: "create_var".to_string(),
+                span: span.clone(),
             });
 
             in_func = true;
@@ -280,843 +491,1350 @@ pub fn tokenize(data: String, meta: &mut Metadata) -> Vec<Token> {
             continue;
         }
 
-        if word == ";" || word == "=" && !in_string {
+        if in_variable_set {
+            tokens.push(Token {
+                ty: TokenTypes::STRING,
+                modifiers: vec![TokenModifiers::ARGS],
+                val: word,
+                span: span.clone(),
+            });
+            in_variable_set = false;
             continue;
         }
 
-        if variables.contains_key(word) {
-            if !in_literal && !in_string {
-                tokens.push(Token {
-                    ty: TokenTypes::VARIABLE,
-                    modifiers: if in_func || in_operator || in_compare {
-                        vec![TokenModifiers::ARGS]
-                    } else {
-                        vec![]
-                    },
-                    val: word.to_string(),
-                });
-            }
-
+        if word == "=" {
             continue;
         }
 
-        let index_split: Vec<&str> = word.split('[').collect();
+        // `else` and `else if` are passed to `if` as markers between its branches.
+        if word == "else" {
+            tokens.push(Token {
+                ty: TokenTypes::NAME,
+                modifiers: vec![TokenModifiers::ARGS],
+                val: word,
+                span: span.clone(),
+            });
+            continue;
+        }
 
-        if index_split.len() == 2 {
-            let variable_get = variables.get(index_split[0]);
-            if variable_get.is_some() {
-                let index = index_split[1];
-                let index = &index[..index.len() - 1];
+        if word == "if"
+            && tokens
+                .last()
+                .is_some_and(|t| t.ty == TokenTypes::NAME && t.val == "else")
+        {
+            tokens.last_mut().unwrap().val = "else if".to_string();
+            continue;
+        }
 
-                let index: usize = match index.parse() {
-                    Ok(idx) => idx,
-                    Err(_) => {
-                        eprintln!(
-                            "Error on line {}: Variables can only be indexed by natural numbers",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
+        // A variable holding a function calls it when it starts a statement.
+        // An `fn` is found through the variable when it is called, a named function by its key.
+        if let Some(Some(function)) = variables.with(&word, Value::function) {
+            if tokens.is_empty() {
+                let val = match function {
+                    Function::Named(key) => key.to_string(),
+                    Function::Anonymous(_) => word.to_string(),
                 };
 
-                let variable = variable_get.unwrap();
-
-                match &variable.value.ty {
-                    TokenTypes::ARRAY => {
-                        let items: Vec<&str> = variable
-                            .value
-                            .val
-                            .split('\x05')
-                            .map(|i| i.trim())
-                            .filter(|&i| !i.is_empty())
-                            .collect();
-
-                        let item = items.get(index);
-
-                        let item = match item {
-                            Some(&item) => item,
-                            None => {
-                                eprintln!(
-                                    "Error on line {}: Indexing overload on array",
-                                    meta.line_count + 1
-                                );
-                                exit(1);
-                            }
-                        };
-
-                        let tokens_: Vec<Token> = tokenize(item.to_string(), meta)
-                            .iter()
-                            .map(|t| Token {
-                                ty: t.ty.clone(),
-                                modifiers: if in_func || in_compare {
-                                    vec![TokenModifiers::ARGS]
-                                } else {
-                                    vec![]
-                                },
-                                val: t.val.to_string(),
-                            })
-                            .collect();
-
-                        tokens.extend(tokens_);
-                        continue;
-                    }
-                    TokenTypes::STRING => {
-                        let val = &variable.value.val;
-
-                        let subs = val.chars().nth(index);
-
-                        let substring = match subs {
-                            Some(s) => s,
-                            None => {
-                                eprintln!(
-                                    "Error on line {}: Indexing overload on string",
-                                    meta.line_count + 1
-                                );
-                                exit(1);
-                            }
-                        };
-
-                        tokens.push(Token {
-                            ty: TokenTypes::STRING,
-                            modifiers: if in_func || in_compare {
-                                vec![TokenModifiers::ARGS]
-                            } else {
-                                vec![]
-                            },
-                            val: substring.to_string(),
-                        });
+                tokens.push(Token {
+                    ty: TokenTypes::CUSTOMFUNC,
+                    modifiers: vec![],
+                    val,
+                    span: span.clone(),
+                });
 
-                        continue;
-                    }
-                    ty => {
-                        eprintln!(
-                            "Error on line {}: Cannot index type {ty:?}",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                }
+                in_func = true;
+
+                continue;
             }
         }
 
-        if word == "==" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+        if !variables.contains(&word) {
+            let key = variables.function_key(&word);
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No first value for comparison operator '==' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+            if custom_funcs.contains_key(&key) {
+                // Anywhere else than the start, a function's name is the function as a value.
+                if !tokens.is_empty() {
+                    tokens.push(Token {
+                        ty: TokenTypes::FUNCTION,
+                        modifiers,
+                        val: key,
+                        span: span.clone(),
+                    });
+                    continue;
+                }
 
                 tokens.push(Token {
-                    ty: TokenTypes::FUNC,
+                    ty: TokenTypes::CUSTOMFUNC,
                     modifiers: vec![],
-                    val: "eq".to_string(),
-                });
-
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
+                    val: key,
+                    span: span.clone(),
                 });
 
-                in_compare = true;
-            };
+                in_func = true;
 
-            continue;
-        }
-        if word == "!=" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+                continue;
+            }
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No first value for comparison operator '!=' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
+            if functions.contains_key(word.as_str()) {
+                // `fn` and `if` make a value, so they can be an argument.
+                let modifiers = if word == "fn" || word == "if" {
+                    modifiers
+                } else {
+                    vec![]
                 };
 
                 tokens.push(Token {
                     ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "ne".to_string(),
-                });
-
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
+                    modifiers,
+                    val: word,
+                    span: span.clone(),
                 });
 
-                in_compare = true;
-            };
+                in_func = true;
 
-            continue;
+                continue;
+            }
         }
-        if word == "lazy=" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No first value for comparison operator 'lazy=' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+        tokens.extend(classify(Lexeme::Word(word), span, modifiers, &variables));
+    }
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "lazy_eq".to_string(),
-                });
+    tokens
+}
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+/// Joins the lines of a statement that goes on while a `(`, `[`, `#{` or string is still open.
+/// Gives the whole statement once it is complete (with `meta.span` moved to its first line), or
+/// `None` while it needs more lines.
+fn continue_statement(data: String, meta: &mut Metadata) -> Option<String> {
+    let data = if meta.pending.is_empty() {
+        data
+    } else {
+        meta.span.line -= meta.pending.matches('\n').count() + 1;
+
+        let mut statement = mem::take(meta.pending);
+        statement.push('\n');
+        statement.push_str(&data);
+        statement
+    };
+
+    if Scanner::new(&data, &meta.span).unclosed().is_some() {
+        *meta.pending = data;
+        return None;
+    }
 
-                in_compare = true;
-            };
+    Some(data)
+}
 
-            continue;
+/// Reports a statement or block that was still open when its file ended, and stops. `last_line`
+/// is the number of the file's last line.
+pub fn check_unclosed(
+    pending: &str,
+    scope_depth: usize,
+    scope_token: &str,
+    start: &Span,
+    last_line: usize,
+) {
+    if let Some(error) = unclosed(pending, scope_depth, scope_token, start, last_line) {
+        eprintln!("{error}");
+        exit(1);
+    }
+}
+
+/// The error of [`check_unclosed`], if there is one. An open block is reported at the line that
+/// opened it, which is before the lines of its body that were read.
+fn unclosed(
+    pending: &str,
+    scope_depth: usize,
+    scope_token: &str,
+    start: &Span,
+    last_line: usize,
+) -> Option<String> {
+    if !pending.is_empty() {
+        let span = Span {
+            line: last_line - pending.matches('\n').count(),
+            ..start.clone()
+        };
+        let scanner = Scanner::new(pending, &span);
+
+        if let Some(i) = scanner.unclosed() {
+            return Some(match scanner.chars[i] {
+                '"' => format!("Error at {}: Unterminated string!", scanner.span(i)),
+                '(' => format!("Error at {}: Missing closing ')' !", scanner.span(i)),
+                '[' => format!("Error at {}: Missing closing ']' !", scanner.span(i)),
+                _ => format!("Error at {}: Missing closing '}}' !", scanner.span(i - 1)),
+            });
         }
-        if word == "lazy!=" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+    }
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No first value for comparison operator 'lazy!=' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+    if scope_depth > 0 {
+        let span = Span {
+            line: last_line - scope_token.lines().count(),
+            ..start.clone()
+        };
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "lazy_ne".to_string(),
-                });
+        return Some(format!("Error at {span}: Missing closing '}}' !"));
+    }
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+    None
+}
 
-                in_compare = true;
-            };
+/// Adds a line to the body of the scope that is being read, exactly as written. Braces decide
+/// where a scope ends, so indentation does not matter.
+fn store_scope_line(line: &str, meta: &mut Metadata) {
+    // Scopes inside scopes are read again when they are run, so only warn the first time.
+    if *meta.scope == 0 {
+        check_indentation(line, meta);
+    }
 
-            continue;
-        }
+    meta.scope_token.push_str(line);
+    meta.scope_token.push('\n');
+}
 
-        if word == ">" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+/// Warns about a line whose indentation mixes tabs and spaces, or uses different ones from the
+/// lines above it in the same scope.
+fn check_indentation(line: &str, meta: &Metadata) {
+    if let Some(warning) = indentation_warning(line, meta) {
+        eprintln!("{warning}");
+    }
+}
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No first value for comparison operator '>' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+/// The warning of [`check_indentation`], if there is one.
+fn indentation_warning(line: &str, meta: &Metadata) -> Option<String> {
+    let name = |c: char| if c == '\t' { "tabs" } else { "spaces" };
+    let indent: Vec<char> = line
+        .chars()
+        .take_while(|&c| c == ' ' || c == '\t')
+        .collect();
+
+    if indent.contains(&' ') && indent.contains(&'\t') {
+        return Some(format!(
+            "Warning at {}: This line is indented with both tabs and spaces!",
+            meta.span
+        ));
+    }
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "gt".to_string(),
-                });
+    let expected = meta
+        .scope_token
+        .lines()
+        .find_map(|l| l.chars().next().filter(|&c| c == ' ' || c == '\t'));
+
+    match (expected, indent.first()) {
+        (Some(expected), Some(&found)) if expected != found => Some(format!(
+            "Warning at {}: This line is indented with {} but the lines above it use {}!",
+            meta.span,
+            name(found),
+            name(expected)
+        )),
+        _ => None,
+    }
+}
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+/// Turns a lexeme that stands for a value into its token(s).
+fn classify(
+    lexeme: Lexeme,
+    span: Span,
+    modifiers: Vec<TokenModifiers>,
+    variables: &Environment,
+) -> Vec<Token> {
+    let word = match lexeme {
+        Lexeme::Str(string) => {
+            return vec![Token {
+                ty: TokenTypes::STRING,
+                modifiers,
+                val: string,
+                span,
+            }]
+        }
+        Lexeme::Template(template) => {
+            return vec![Token {
+                ty: TokenTypes::TEMPLATE,
+                modifiers,
+                val: template,
+                span,
+            }]
+        }
+        Lexeme::Literal(literal) => {
+            return vec![Token {
+                ty: TokenTypes::LITERAL,
+                modifiers,
+                val: literal,
+                span,
+            }]
+        }
+        Lexeme::Array(items) => {
+            return vec![Token {
+                ty: TokenTypes::ARRAY,
+                modifiers,
+                val: items.join("\x05"),
+                span,
+            }]
+        }
+        Lexeme::Map(entries) => {
+            return vec![Token {
+                ty: TokenTypes::MAP,
+                modifiers,
+                val: entries.join("\x05"),
+                span,
+            }]
+        }
+        // Items are looked up when the line runs, so the index can be any value.
+        Lexeme::Index(..) | Lexeme::Slice(..) => {
+            return vec![Token {
+                ty: TokenTypes::LITERAL,
+                modifiers,
+                val: item_code(&lexeme),
+                span,
+            }]
+        }
+        Lexeme::Scope | Lexeme::Block(_) => {
+            eprintln!("Error at {}: Unexpected '{{' !", span);
+            exit(1);
+        }
+        Lexeme::Word(word) => word,
+    };
+
+    if word == "None" {
+        return vec![Token {
+            ty: TokenTypes::NONE,
+            modifiers,
+            val: "None".to_string(),
+            span,
+        }];
+    }
+    if word == "true" || word == "false" {
+        return vec![Token {
+            ty: TokenTypes::BOOL,
+            modifiers,
+            val: word,
+            span,
+        }];
+    }
 
-                in_operator = true;
-            };
+    if variables.contains(&word) {
+        return vec![Token {
+            ty: TokenTypes::VARIABLE,
+            modifiers,
+            val: word,
+            span,
+        }];
+    }
 
-            continue;
+    if let Some((op, _, operand, _)) = infix_operator(&word) {
+        eprintln!("Error at {}: {}", span, operand.missing(op));
+        exit(1);
+    }
+
+    if is_number(&word) {
+        // Integers too large for 64 bits are still integers.
+        if word.parse::<BigInt>().is_ok() {
+            return vec![Token {
+                ty: TokenTypes::INT,
+                modifiers,
+                val: word,
+                span,
+            }];
         }
-        if word == "<" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No first value for comparison operator '<' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+        if word.parse::<f64>().is_ok() {
+            return vec![Token {
+                ty: TokenTypes::FLOAT,
+                modifiers,
+                val: word,
+                span,
+            }];
+        }
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "lt".to_string(),
-                });
+        if let Some(decimal) = word.strip_suffix('d') {
+            if decimal.parse::<BigDecimal>().is_ok() {
+                return vec![Token {
+                    ty: TokenTypes::DECIMAL,
+                    modifiers,
+                    val: decimal.to_string(),
+                    span,
+                }];
+            }
+        }
+    }
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+    vec![Token {
+        ty: TokenTypes::NAME,
+        modifiers,
+        val: word,
+        span,
+    }]
+}
 
-                in_operator = true;
-            };
+/// Parses the expression starting at `pos` with precedence climbing, stopping before any infix
+/// operator that binds less tightly than `min_precedence`.
+///
+/// Examples:
+///
+/// ```rust
+/// // 1 + 2 * 3
+/// let expr = parse_expr(&lexemes, &mut 0, 0);
+///
+/// assert_eq!(
+///     expr,
+///     Expr::Binary(1, Box::new(Expr::Atom(0)), Box::new(Expr::Binary(3, ..)))
+/// );
+/// ```
+fn parse_expr(lexemes: &[Scanned], pos: &mut usize, min_precedence: u8) -> Expr {
+    let prefix = match &lexemes[*pos].0 {
+        Lexeme::Word(word) => prefix_operator(word),
+        _ => None,
+    };
+
+    let mut left = if let Some(&(_, _, precedence)) = prefix.filter(|_| *pos + 1 < lexemes.len()) {
+        let op = *pos;
+        *pos += 1;
+
+        Expr::Prefix(op, Box::new(parse_expr(lexemes, pos, precedence)))
+    } else {
+        *pos += 1;
+
+        Expr::Atom(*pos - 1)
+    };
+
+    while let Some((Lexeme::Word(word), span, _)) = lexemes.get(*pos) {
+        let (op, precedence) = match infix_operator(word) {
+            Some(&(op, _, _, precedence)) if precedence >= min_precedence => (op, precedence),
+            _ => break,
+        };
 
-            continue;
+        let index = *pos;
+        *pos += 1;
+
+        if *pos >= lexemes.len() {
+            eprintln!("Error at {}: No second value for operator '{op}' !", span);
+            exit(1);
         }
 
-        if word == "+" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+        // Assignments group to the right, everything else to the left.
+        let next = if precedence == ASSIGNMENT {
+            precedence
+        } else {
+            precedence + 1
+        };
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No first number for operator '+' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+        let right = parse_expr(lexemes, pos, next);
+        left = Expr::Binary(index, Box::new(left), Box::new(right));
+    }
 
-                match first.ty {
-                    TokenTypes::INT | TokenTypes::FLOAT | TokenTypes::VARIABLE => {}
-                    _ => {
-                        eprintln!(
-                            "Error on line {}: No first number for operator '+' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                }
+    left
+}
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "sum".to_string(),
-                });
+/// Turns an expression that a line starts with into a call of its operator's function. Operands
+/// that are expressions themselves become literals, so they are only run when the function
+/// needs them.
+fn call_operator(
+    expr: &Expr,
+    lexemes: &[Scanned],
+    scanner: &Scanner,
+    variables: &Environment,
+) -> Vec<Token> {
+    let operand = |expr: &Expr| match expr {
+        Expr::Atom(i) => classify(
+            lexemes[*i].0.clone(),
+            lexemes[*i].1.clone(),
+            vec![TokenModifiers::ARGS],
+            variables,
+        ),
+        expr => vec![expr_literal(
+            expr,
+            lexemes,
+            scanner,
+            vec![TokenModifiers::ARGS],
+        )],
+    };
+
+    let (index, func, operands) = match expr {
+        Expr::Prefix(index, right) => {
+            let (op, func, _) = match &lexemes[*index].0 {
+                Lexeme::Word(word) => prefix_operator(word).unwrap(),
+                _ => unreachable!(),
+            };
 
-                tokens.push(Token {
+            let mut operands = vec![];
+
+            if *op == "-" {
+                operands.push(Token {
+                    ty: TokenTypes::INT,
                     modifiers: vec![TokenModifiers::ARGS],
-                    ..first
+                    val: "0".to_string(),
+                    span: lexemes[*index].1.clone(),
                 });
+            }
 
-                in_operator = true;
+            operands.extend(operand(right));
+
+            (*index, *func, operands)
+        }
+        // The types of the operands are checked by the function when it runs, so that `try` can
+        // catch a wrong one.
+        Expr::Binary(index, left, right) => {
+            let func = match &lexemes[*index].0 {
+                Lexeme::Word(word) => &infix_operator(word).unwrap().1,
+                _ => unreachable!(),
             };
 
-            continue;
+            let mut operands = operand(left);
+            operands.extend(operand(right));
+
+            (*index, *func, operands)
         }
-        if word == "-" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+        Expr::Atom(i) => return operand(&Expr::Atom(*i)),
+    };
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No first number for operator '-' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+    let mut tokens = vec![Token {
+        ty: TokenTypes::FUNC,
+        modifiers: vec![],
+        val: func.to_string(),
+        span: lexemes[index].1.clone(),
+    }];
 
-                match first.ty {
-                    TokenTypes::INT | TokenTypes::FLOAT | TokenTypes::VARIABLE => {}
-                    _ => {
-                        eprintln!(
-                            "Error on line {}: No first number for operator '-' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                }
+    tokens.extend(operands);
+    tokens
+}
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "difference".to_string(),
-                });
+/// Makes a literal out of the source text of `expr`, to be run when it is needed.
+fn expr_literal(
+    expr: &Expr,
+    lexemes: &[Scanned],
+    scanner: &Scanner,
+    modifiers: Vec<TokenModifiers>,
+) -> Token {
+    let (first, last) = expr.bounds();
+    let (start, end) = (lexemes[first].2.start, lexemes[last].2.end);
+
+    Token {
+        ty: TokenTypes::LITERAL,
+        modifiers,
+        val: scanner.text(start, end),
+        span: scanner.span(start),
+    }
+}
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+fn infix_operator(word: &str) -> Option<&'static InfixOperator> {
+    INFIX_OPERATORS.iter().find(|(op, ..)| *op == word)
+}
 
-                in_operator = true;
-            };
+fn prefix_operator(word: &str) -> Option<&'static (&'static str, &'static str, u8)> {
+    PREFIX_OPERATORS.iter().find(|(op, ..)| *op == word)
+}
+
+/// Splits a line into [`Lexeme`]s, one character at a time, along with where each one starts
+/// and the characters it covers.
+///
+/// Strings are kept exactly as written (including runs of spaces and tabs), and `(`, `[`, `"`
+/// and operators do not need spaces around them.
+///
+/// Examples:
+///
+/// ```rust
+/// let lexemes = scan(&Scanner::new("say(1+2)", &meta.span));
+///
+/// assert_eq!(lexemes[0].0, Lexeme::Word("say".to_string()));
+/// assert_eq!(lexemes[1].0, Lexeme::Literal("1+2".to_string()));
+/// assert_eq!(lexemes[1].1.column, 5);
+/// assert_eq!(lexemes[1].2, 3..8);
+/// ```
+fn scan(scanner: &Scanner) -> Vec<Scanned> {
+    let chars = &scanner.chars;
+    let mut lexemes: Vec<Scanned> = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i];
 
+        if ch.is_whitespace() || ch == ',' || ch == ';' {
+            i += 1;
             continue;
         }
-        if word == "*" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No first number for operator '*' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+        match ch {
+            '"' => {
+                let end = scanner.string_end(i);
 
-                match first.ty {
-                    TokenTypes::INT | TokenTypes::FLOAT | TokenTypes::VARIABLE => {}
-                    _ => {
-                        eprintln!(
-                            "Error on line {}: No first number for operator '*' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
+                if scanner.has_placeholder(i + 1, end) {
+                    lexemes.push((
+                        Lexeme::Template(scanner.text(i + 1, end)),
+                        scanner.span(i + 1),
+                        i..end + 1,
+                    ));
+                } else {
+                    lexemes.push((
+                        Lexeme::Str(scanner.unescape(i + 1, end)),
+                        scanner.span(i),
+                        i..end + 1,
+                    ));
                 }
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "product".to_string(),
-                });
+                i = end + 1;
+            }
+            '(' => {
+                let end = scanner.closing_bracket(i);
+                lexemes.push((
+                    Lexeme::Literal(scanner.text(i + 1, end)),
+                    scanner.span(i + 1),
+                    i..end + 1,
+                ));
+                i = end + 1;
+            }
+            '[' => {
+                let end = scanner.closing_bracket(i);
+                lexemes.push((
+                    Lexeme::Array(scanner.split_items(i + 1, end)),
+                    scanner.span(i),
+                    i..end + 1,
+                ));
+                i = end + 1;
+            }
+            '#' if chars.get(i + 1) == Some(&'{') => {
+                let end = scanner.closing_bracket(i + 1);
+                lexemes.push((
+                    Lexeme::Map(scanner.map_entries(i + 2, end)),
+                    scanner.span(i),
+                    i..end + 1,
+                ));
+                i = end + 1;
+            }
+            '{' => match scanner.find_closing_bracket(i) {
+                Some(end) => {
+                    lexemes.push((
+                        Lexeme::Block(scanner.text(i + 1, end)),
+                        scanner.span(i + 1),
+                        i..end + 1,
+                    ));
+                    i = end + 1;
+                }
+                None => {
+                    lexemes.push((Lexeme::Scope, scanner.span(i), i..i + 1));
+                    break;
+                }
+            },
+            _ => {
+                let negative_number = ch == '-'
+                    && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())
+                    && (i == 0 || !ends_value(chars[i - 1]));
+
+                if !negative_number {
+                    if let Some(op) = OPERATORS.iter().find(|op| scanner.starts_with_at(i, op)) {
+                        lexemes.push((
+                            Lexeme::Word(op.to_string()),
+                            scanner.span(i),
+                            i..i + op.len(),
+                        ));
+                        i += op.len();
+                        continue;
+                    }
+                }
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+                let start = i;
+                i += 1;
 
-                in_operator = true;
-            };
+                while i < chars.len() && !is_delimiter(chars[i]) {
+                    i += 1;
+                }
 
-            continue;
-        }
-        if word == "/" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+                let mut word = scanner.text(start, i);
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No first number for operator '/' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
+                if word == "lazy" {
+                    for op in ["=", "!="] {
+                        if scanner.starts_with_at(i, op) {
+                            word.push_str(op);
+                            i += op.len();
+                            break;
+                        }
                     }
-                };
+                }
 
-                match first.ty {
-                    TokenTypes::INT | TokenTypes::FLOAT | TokenTypes::VARIABLE => {}
-                    _ => {
-                        eprintln!(
-                            "Error on line {}: No first number for operator '/' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
+                if chars.get(i) == Some(&'[') {
+                    let mut end = scanner.closing_bracket(i);
+                    let mut lexeme = scanner.index(word, i, end);
+
+                    // `name[i][j]` reads item `j` of item `i`.
+                    while chars.get(end + 1) == Some(&'[') {
+                        let open = end + 1;
+                        end = scanner.closing_bracket(open);
+                        lexeme = scanner.index(format!("({})", item_code(&lexeme)), open, end);
                     }
+
+                    lexemes.push((lexeme, scanner.span(start), start..end + 1));
+                    i = end + 1;
+                    continue;
                 }
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "quotient".to_string(),
-                });
+                lexemes.push((Lexeme::Word(word), scanner.span(start), start..i));
+            }
+        }
+    }
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+    lexemes
+}
 
-                in_operator = true;
-            };
+/// The characters of a piece of source, along with the line and column of each one.
+struct Scanner {
+    chars: Vec<char>,
+    positions: Vec<(usize, usize)>,
+    file: Arc<str>,
+}
 
-            continue;
+impl Scanner {
+    /// Creates a scanner for `data`, which starts at `start` in its file.
+    fn new(data: &str, start: &Span) -> Scanner {
+        let chars: Vec<char> = data.chars().collect();
+        let mut positions = Vec::with_capacity(chars.len() + 1);
+        let (mut line, mut column) = (start.line, start.column);
+
+        for &c in &chars {
+            positions.push((line, column));
+
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
         }
 
-        if word == "+=" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+        positions.push((line, column));
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No variable for operator '+=' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+        Scanner {
+            chars,
+            positions,
+            file: start.file.clone(),
+        }
+    }
 
-                match first.ty {
-                    TokenTypes::VARIABLE => {}
-                    _ => {
-                        eprintln!(
-                            "Error on line {}: No variable for operator '+=' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                }
+    /// The span of the character at `i`.
+    fn span(&self, i: usize) -> Span {
+        let (line, column) = self.positions[i.min(self.positions.len() - 1)];
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "add_assign".to_string(),
-                });
+        Span {
+            file: self.file.clone(),
+            line,
+            column,
+        }
+    }
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+    /// The text between `start` (inclusive) and `end` (exclusive).
+    fn text(&self, start: usize, end: usize) -> String {
+        self.chars[start..end].iter().collect()
+    }
 
-                in_operator = true;
-            };
+    fn starts_with_at(&self, at: usize, pattern: &str) -> bool {
+        pattern
+            .chars()
+            .enumerate()
+            .all(|(i, p)| self.chars.get(at + i) == Some(&p))
+    }
 
-            continue;
+    /// Finds the index of the `"` that closes the string starting at `start`, skipping over
+    /// escaped quotes.
+    fn string_end(&self, start: usize) -> usize {
+        let mut i = start + 1;
+
+        while i < self.chars.len() {
+            match self.chars[i] {
+                '\\' => i = self.escape_end(i),
+                '"' => return i,
+                '{' => i = self.closing_bracket(i) + 1,
+                _ => i += 1,
+            }
         }
 
-        if word == "-=" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+        eprintln!("Error at {}: Unterminated string!", self.span(start));
+        exit(1);
+    }
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No variable for operator '-=' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+    /// Returns the index just after the escape sequence starting at the `\\` at `start`, so that
+    /// the braces of `\u{...}` are not read as a placeholder.
+    fn escape_end(&self, start: usize) -> usize {
+        if self.starts_with_at(start + 1, "u{") {
+            let mut i = start + 3;
 
-                match first.ty {
-                    TokenTypes::VARIABLE => {}
-                    _ => {
-                        eprintln!(
-                            "Error on line {}: No variable for operator '-=' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
+            while i < self.chars.len() && self.chars[i] != '}' && self.chars[i] != '"' {
+                i += 1;
+            }
+
+            if i < self.chars.len() && self.chars[i] == '}' {
+                return i + 1;
+            }
+
+            return i;
+        }
+
+        start + 2
+    }
+
+    /// Whether the string between `start` and `end` has an unescaped `{`.
+    fn has_placeholder(&self, start: usize, end: usize) -> bool {
+        let mut i = start;
+
+        while i < end {
+            match self.chars[i] {
+                '\\' => i = self.escape_end(i),
+                '{' => return true,
+                _ => i += 1,
+            }
+        }
+
+        false
+    }
+
+    /// Splits the text between `start` and `end` on the commas that are not inside strings or
+    /// brackets. The items are kept exactly as written, so that where each one starts can be
+    /// worked out again from where the first one does.
+    fn split_items(&self, start: usize, end: usize) -> Vec<String> {
+        let mut items: Vec<String> = vec![];
+        let mut item_start = start;
+        let mut depth = 0;
+        let mut i = start;
+
+        while i < end {
+            match self.chars[i] {
+                '"' => i = self.string_end(i),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                ',' if depth == 0 => {
+                    items.push(self.text(item_start, i));
+                    item_start = i + 1;
                 }
+                _ => {}
+            }
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "subtract_assign".to_string(),
-                });
+            i += 1;
+        }
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+        items.push(self.text(item_start, end));
 
-                in_operator = true;
-            };
+        items
+    }
 
-            continue;
+    /// Splits the `name[i][j]...` written between `start` and `end` into the name, and the code
+    /// and place of each index.
+    fn index_chain(&self, start: usize, end: usize) -> (String, Vec<(String, usize)>) {
+        let mut open = start;
+
+        while self.chars[open] != '[' {
+            open += 1;
         }
 
-        if word == "*=" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+        let name = self.text(start, open);
+        let mut indices: Vec<(String, usize)> = vec![];
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No variable for operator '*=' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+        while open < end && self.chars[open] == '[' {
+            let close = self.closing_bracket(open);
+
+            indices.push((self.text(open + 1, close), open + 1));
+            open = close + 1;
+        }
+
+        (name, indices)
+    }
 
-                match first.ty {
-                    TokenTypes::VARIABLE => {}
-                    _ => {
+    /// Reads the `[...]` between `open` and `close` after `name`, which is a slice if it has colons
+    /// that are not inside strings or brackets.
+    fn index(&self, name: String, open: usize, close: usize) -> Lexeme {
+        let mut bounds: Vec<String> = vec![];
+        let mut bound_start = open + 1;
+        let mut depth = 0;
+        let mut i = open + 1;
+
+        while i < close {
+            match self.chars[i] {
+                '"' => i = self.string_end(i),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                ':' if depth == 0 => {
+                    bounds.push(self.text(bound_start, i).trim().to_string());
+                    bound_start = i + 1;
+
+                    if bounds.len() == 3 {
                         eprintln!(
-                            "Error on line {}: No variable for operator '*=' !",
-                            meta.line_count + 1
+                            "Error at {}: A slice has at most a start, an end and a step!",
+                            self.span(i)
                         );
                         exit(1);
                     }
                 }
+                _ => {}
+            }
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "multiply_assign".to_string(),
-                });
+            i += 1;
+        }
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+        let last = self.text(bound_start, close);
 
-                in_operator = true;
-            };
+        if bounds.is_empty() {
+            return Lexeme::Index(name, last);
+        }
 
-            continue;
+        bounds.push(last.trim().to_string());
+        Lexeme::Slice(name, bounds)
+    }
+
+    /// Splits the text between `start` and `end` into the `key: value` entries of a map, on the
+    /// commas and colons that are not inside strings or brackets. Like [`Scanner::split_items`],
+    /// the entries are kept exactly as written, with `\x06` in place of the colon.
+    fn map_entries(&self, start: usize, end: usize) -> Vec<String> {
+        let mut entries: Vec<String> = vec![];
+        let mut entry_start = start;
+        let mut colon: Option<usize> = None;
+        let mut depth = 0;
+        let mut i = start;
+
+        while i < end {
+            match self.chars[i] {
+                '"' => i = self.string_end(i),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                ':' if depth == 0 && colon.is_none() => colon = Some(i),
+                ',' if depth == 0 => {
+                    entries.push(self.map_entry(entry_start, colon, i));
+                    entry_start = i + 1;
+                    colon = None;
+                }
+                _ => {}
+            }
+
+            i += 1;
         }
 
-        if word == "/=" && !in_string {
-            if !in_literal {
-                let first = tokens.pop();
+        entries.push(self.map_entry(entry_start, colon, end));
 
-                let first = match first {
-                    Some(token) => token,
-                    None => {
-                        eprintln!(
-                            "Error on line {}: No variable for operator '/=' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                };
+        entries
+    }
 
-                match first.ty {
-                    TokenTypes::VARIABLE => {}
-                    _ => {
-                        eprintln!(
-                            "Error on line {}: No variable for operator '/=' !",
-                            meta.line_count + 1
-                        );
-                        exit(1);
-                    }
-                }
+    /// Reads one `key: value` entry of a map, whose first colon is at `colon`. An empty entry,
+    /// like the one after a trailing comma, is kept as it is.
+    fn map_entry(&self, start: usize, colon: Option<usize>, end: usize) -> String {
+        if self.text(start, end).trim().is_empty() {
+            return self.text(start, end);
+        }
 
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: "divide_assign".to_string(),
-                });
+        let (key, value) = match colon {
+            Some(colon) => (self.text(start, colon), self.text(colon + 1, end)),
+            None => (String::new(), String::new()),
+        };
 
-                tokens.push(Token {
-                    modifiers: vec![TokenModifiers::ARGS],
-                    ..first
-                });
+        if key.trim().is_empty() || value.trim().is_empty() {
+            let first = (start..end).find(|&i| !self.chars[i].is_whitespace());
+            eprintln!(
+                "Error at {}: Expected `key: value` in map!",
+                self.span(first.unwrap_or(start))
+            );
+            exit(1);
+        }
 
-                in_operator = true;
+        format!("{key}\x06{value}")
+    }
+
+    /// Reads the string between `start` and `end`, replacing its escape sequences (`\n`, `\t`,
+    /// `\r`, `\0`, `\\`, `\"`, `\{`, `\}`, `\xHH` and `\u{H...}`) with the characters they
+    /// stand for.
+    fn unescape(&self, start: usize, end: usize) -> String {
+        let mut string = String::new();
+        let mut i = start;
+
+        while i < end {
+            if self.chars[i] != '\\' {
+                string.push(self.chars[i]);
+                i += 1;
+                continue;
+            }
+
+            let (ch, len) = match self.chars[i + 1] {
+                'n' => (Some('\n'), 2),
+                't' => (Some('\t'), 2),
+                'r' => (Some('\r'), 2),
+                '0' => (Some('\0'), 2),
+                '\\' => (Some('\\'), 2),
+                '"' => (Some('"'), 2),
+                '{' => (Some('{'), 2),
+                '}' => (Some('}'), 2),
+                'x' => {
+                    let digits = self.text(i + 2, (i + 4).min(end));
+
+                    match u8::from_str_radix(&digits, 16) {
+                        Ok(code) if digits.len() == 2 => (Some(code as char), 4),
+                        _ => (None, 0),
+                    }
+                }
+                'u' => {
+                    let close = self.chars[i + 2..end].iter().position(|&c| c == '}');
+
+                    match close {
+                        Some(close) if self.chars[i + 2] == '{' => {
+                            let digits = self.text(i + 3, i + 2 + close);
+
+                            match u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                            {
+                                Some(ch) => (Some(ch), close + 3),
+                                None => (None, 0),
+                            }
+                        }
+                        _ => (None, 0),
+                    }
+                }
+                _ => (None, 0),
             };
 
-            continue;
+            match ch {
+                Some(ch) => {
+                    string.push(ch);
+                    i += len;
+                }
+                None => {
+                    eprintln!(
+                        "Error at {}: Invalid escape sequence '\\{}' !",
+                        self.span(i),
+                        self.chars[i + 1]
+                    );
+                    exit(1);
+                }
+            }
         }
 
-        if word.parse::<i64>().is_ok() && !in_string {
-            if !in_literal {
-                tokens.push(Token {
-                    ty: TokenTypes::INT,
-                    modifiers: if in_func || in_operator || in_compare {
-                        if in_operator {
-                            in_operator = false;
-                        }
+        string
+    }
 
-                        vec![TokenModifiers::ARGS]
-                    } else {
-                        vec![]
-                    },
-                    val: word.to_string(),
-                })
+    /// Finds the index of the `"` that closes the string starting at `start` like
+    /// [`Scanner::string_end`], but returns the end of the text instead of failing when the
+    /// string is not closed.
+    fn skip_string(&self, start: usize) -> usize {
+        let mut depth = 0;
+        let mut i = start + 1;
+
+        while i < self.chars.len() {
+            match self.chars[i] {
+                '\\' if depth == 0 => {
+                    i = self.escape_end(i);
+                    continue;
+                }
+                '"' if depth == 0 => return i,
+                '"' => i = self.skip_string(i),
+                '{' => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                _ => {}
             }
 
-            continue;
+            i += 1;
         }
 
-        if word.parse::<f64>().is_ok() && !in_string {
-            if !in_literal {
-                tokens.push(Token {
-                    ty: TokenTypes::FLOAT,
-                    modifiers: if in_func || in_operator || in_compare {
-                        if in_operator {
-                            in_operator = false;
-                        }
+        self.chars.len()
+    }
 
-                        vec![TokenModifiers::ARGS]
-                    } else {
-                        vec![]
-                    },
-                    val: word.to_string(),
-                })
-            }
+    /// Finds the index of the bracket that closes the one at `start`, skipping over strings and
+    /// nested brackets of the same kind.
+    fn closing_bracket(&self, start: usize) -> usize {
+        match self.find_closing_bracket(start) {
+            Some(end) => end,
+            None => {
+                let close = match self.chars[start] {
+                    '(' => ')',
+                    '[' => ']',
+                    _ => '}',
+                };
 
-            continue;
+                eprintln!("Error at {}: Missing closing '{close}' !", self.span(start));
+                exit(1);
+            }
         }
+    }
 
-        if word == "{" && !in_string {
-            *meta.in_scope = true;
+    /// Like [`Scanner::closing_bracket`], but gives `None` if the bracket is not closed.
+    fn find_closing_bracket(&self, start: usize) -> Option<usize> {
+        let open = self.chars[start];
+        let close = match open {
+            '(' => ')',
+            '[' => ']',
+            _ => '}',
+        };
 
-            *meta.current_tokens = tokens.clone();
+        let mut depth = 0;
+        let mut i = start;
 
-            return vec![];
-        }
+        while i < self.chars.len() {
+            match self.chars[i] {
+                '"' => i = self.string_end(i),
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
 
-        if word.starts_with('"') {
-            if in_string {
-                string_token.push(' ');
-                if !in_literal {
-                    tokens.push(Token {
-                        ty: TokenTypes::STRING,
-                        modifiers: if in_func || in_compare {
-                            vec![TokenModifiers::ARGS]
-                        } else {
-                            vec![]
-                        },
-                        val: string_token.clone(),
-                    });
+                    if depth == 0 {
+                        return Some(i);
+                    }
                 }
-
-                in_string = false;
-                continue;
+                _ => {}
             }
 
-            in_string = true;
+            i += 1;
+        }
 
-            string_token.push_str(if word == "\"" { " " } else { &word[1..] });
+        None
+    }
 
-            if word.ends_with('"') {
-                in_string = false;
+    /// Finds the outermost `(`, `[`, `#{` or `"` that is not closed by the end of the text.
+    fn unclosed(&self) -> Option<usize> {
+        let mut open: Vec<usize> = vec![];
+        let mut i = 0;
+
+        while i < self.chars.len() {
+            match self.chars[i] {
+                '"' => {
+                    if self.skip_string(i) >= self.chars.len() {
+                        open.push(i);
+                        break;
+                    }
 
-                string_token.pop();
-                if !in_literal {
-                    tokens.push(Token {
-                        ty: TokenTypes::STRING,
-                        modifiers: if in_func || in_compare {
-                            vec![TokenModifiers::ARGS]
-                        } else {
-                            vec![]
-                        },
-                        val: string_token.clone(),
-                    });
+                    i = self.skip_string(i);
                 }
-
-                string_token.clear();
+                '(' | '[' | '{' => open.push(i),
+                ')' | ']' | '}' => {
+                    open.pop();
+                }
+                _ => {}
             }
 
-            continue;
+            i += 1;
         }
 
-        if in_string {
-            string_token.push(' ');
-            string_token.push_str(word);
+        // A `{` that is not part of a `#{` map opens a scope, which goes on differently.
+        open.into_iter()
+            .find(|&i| self.chars[i] != '{' || (i > 0 && self.chars[i - 1] == '#'))
+    }
 
-            if word.ends_with('"') {
-                in_string = false;
+    /// Follows the `{` and `}` of a line inside a scope, starting at `depth`, and gives the index
+    /// of the `}` that brings it back to zero, if there is one.
+    fn block_end(&self, depth: &mut usize) -> Option<usize> {
+        let mut i = 0;
 
-                string_token.pop();
-                if !in_literal {
-                    tokens.push(Token {
-                        ty: TokenTypes::STRING,
-                        modifiers: if in_func || in_compare {
-                            vec![TokenModifiers::ARGS]
-                        } else {
-                            vec![]
-                        },
-                        val: string_token.clone(),
-                    });
-                }
+        while i < self.chars.len() {
+            match self.chars[i] {
+                '"' => i = self.skip_string(i),
+                '{' => *depth += 1,
+                '}' => {
+                    *depth -= 1;
 
-                string_token.clear();
+                    if *depth == 0 {
+                        return Some(i);
+                    }
+                }
+                _ => {}
             }
 
-            continue;
+            i += 1;
         }
 
-        if custom_funcs.contains_key(word) {
-            if !in_literal {
-                tokens.push(Token {
-                    ty: TokenTypes::CUSTOMFUNC,
-                    modifiers: vec![],
-                    val: word.to_string(),
-                });
-            }
+        None
+    }
+}
 
-            in_func = true;
+/// What the start of a line is inside of, as carried from one line to the next by
+/// [`strip_comments`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum LineState {
+    #[default]
+    Code,
+    BlockComment,
+    Str,
+}
+
+/// Removes the comments from a line of source code, keeping any `;` that is inside a string.
+///
+/// A `;` starts a comment that runs to the end of the line, while `;*` starts a block comment
+/// that runs until the next `*;`, which may be on a later line. `state` carries whether a block
+/// comment or a string is still open from one line to the next. Comments are replaced with spaces
+/// so that the columns of the remaining code do not change.
+///
+/// Examples:
+///
+/// ```rust
+/// let mut state = LineState::Code;
+///
+/// assert_eq!(strip_comments("say \"a; b\" ; c", &mut state), "say \"a; b\"");
+/// assert_eq!(strip_comments("say 1 ;* a", &mut state), "say 1");
+/// assert_eq!(strip_comments("b *; say 2", &mut state), "     say 2");
+/// ```
+pub fn strip_comments(line: &str, state: &mut LineState) -> String {
+    // A string left open on the line before is continued by putting its quote back in front.
+    let continues_string = *state == LineState::Str;
+
+    let line = if continues_string {
+        *state = LineState::Code;
+        format!("\"{line}")
+    } else {
+        line.to_string()
+    };
+
+    let scanner = Scanner::new(&line, &Span::default());
+    let chars = &scanner.chars;
+    let mut stripped = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if *state == LineState::BlockComment {
+            if scanner.starts_with_at(i, "*;") {
+                *state = LineState::Code;
+                stripped.push_str("  ");
+                i += 2;
+            } else {
+                stripped.push(if chars[i] == '\t' { '\t' } else { ' ' });
+                i += 1;
+            }
 
             continue;
         }
 
-        if functions.contains_key(word) {
-            if !in_literal {
-                tokens.push(Token {
-                    ty: TokenTypes::FUNC,
-                    modifiers: vec![],
-                    val: word.to_string(),
-                });
-            }
+        match chars[i] {
+            '"' => {
+                let end = scanner.skip_string(i);
 
-            in_func = true;
+                if end >= chars.len() {
+                    *state = LineState::Str;
+                }
 
-            continue;
+                stripped.push_str(&scanner.text(i, (end + 1).min(chars.len())));
+                i = end + 1;
+            }
+            ';' if scanner.starts_with_at(i + 1, "*") => {
+                *state = LineState::BlockComment;
+                stripped.push_str("  ");
+                i += 2;
+            }
+            ';' => break,
+            c => {
+                stripped.push(c);
+                i += 1;
+            }
         }
+    }
 
-        if in_variable_set {
-            tokens.push(Token {
-                ty: TokenTypes::STRING,
-                modifiers: vec![TokenModifiers::ARGS],
-                val: word.to_string(),
-            });
-            in_variable_set = false;
-            continue;
+    if continues_string {
+        stripped.remove(0);
+    }
+
+    if *state == LineState::Str {
+        return stripped;
+    }
+
+    stripped.trim_end().to_string()
+}
+
+/// Splits an interpolated string into its text and the code in its `{...}` placeholders.
+///
+/// Examples:
+///
+/// ```rust
+/// let parts = template_parts(&token); // "Hello {name}!"
+///
+/// assert_eq!(parts[0], TemplatePart::Text("Hello ".to_string()));
+/// assert_eq!(parts[2], TemplatePart::Text("!".to_string()));
+/// ```
+pub fn template_parts(token: &Token) -> Vec<TemplatePart> {
+    let scanner = Scanner::new(&token.val, &token.span);
+    let mut parts: Vec<TemplatePart> = vec![];
+    let mut text_start = 0;
+    let mut i = 0;
+
+    while i < scanner.chars.len() {
+        match scanner.chars[i] {
+            '\\' => i = scanner.escape_end(i),
+            '{' => {
+                let end = scanner.closing_bracket(i);
+
+                parts.push(TemplatePart::Text(scanner.unescape(text_start, i)));
+                parts.push(TemplatePart::Placeholder(
+                    scanner.text(i + 1, end),
+                    scanner.span(i + 1),
+                ));
+
+                text_start = end + 1;
+                i = end + 1;
+            }
+            _ => i += 1,
         }
+    }
 
-        if !in_string {
-            if !in_literal {
-                tokens.push(Token {
-                    ty: TokenTypes::NAME,
-                    modifiers: if in_func || in_compare {
-                        vec![TokenModifiers::ARGS]
+    parts.push(TemplatePart::Text(
+        scanner.unescape(text_start, scanner.chars.len()),
+    ));
+
+    parts
+}
+
+/// The code that reads the item or slice of an [`Lexeme::Index`] or [`Lexeme::Slice`].
+fn item_code(lexeme: &Lexeme) -> String {
+    match lexeme {
+        Lexeme::Index(name, index) => format!("get {name} ({index})"),
+        Lexeme::Slice(name, bounds) => {
+            let bounds: Vec<String> = bounds
+                .iter()
+                .map(|bound| {
+                    if bound.is_empty() {
+                        "None".to_string()
                     } else {
-                        vec![]
-                    },
-                    val: word.to_string(),
-                });
-            }
+                        format!("({bound})")
+                    }
+                })
+                .collect();
 
-            continue;
+            format!("slice {name} {}", bounds.join(" "))
         }
+        _ => unreachable!(),
     }
+}
 
-    tokens
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "\"()[]{},;".contains(c) || OPERATOR_CHARS.contains(&c)
+}
+
+/// Whether `c` can be the last character of a value, in which case a following `-` is an operator
+/// rather than the sign of a number.
+fn ends_value(c: char) -> bool {
+    !is_delimiter(c) || c == ')' || c == ']' || c == '"'
+}
+
+fn is_number(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.')
 }
 
 #[test]
 fn tokenizer() {
-    let tokens = tokenize(
-        "say \"Hello World\"".to_string(),
-        &mut Metadata {
-            line_count: 0,
-            scope: &mut 0,
-            in_scope: &mut false,
-            scope_token: &mut String::new(),
-            current_tokens: &mut vec![],
-        },
-    );
+    use crate::runner::run_lines;
+
+    let tokens = tokenize("say \"Hello World\"".to_string(), &mut run_lines(&[]));
+
+    assert_eq!(
+        tokens,
+        vec![
+            Token {
+                ty: TokenTypes::FUNC,
+                modifiers: vec![],
+                val: "say".to_string(),
+                span: Span::start_of("test.dwn")
+            },
+            Token {
+                ty: TokenTypes::STRING,
+                modifiers: vec![TokenModifiers::ARGS],
+                val: "Hello World".to_string(),
+                span: Span {
+                    column: 5,
+                    ..Span::start_of("test.dwn")
+                }
+            },
+        ]
+    )
+}
+
+#[test]
+fn tokenizer_keeps_string_whitespace() {
+    use crate::runner::run_lines;
+
+    let tokens = tokenize("say(\"  a \t b  \")\"c\"".to_string(), &mut run_lines(&[]));
 
     assert_eq!(
         tokens,
@@ -1124,13 +1842,295 @@ fn tokenizer() {
             Token {
                 ty: TokenTypes::FUNC,
                 modifiers: vec![],
-                val: "say".to_string()
+                val: "say".to_string(),
+                span: Span::start_of("test.dwn")
+            },
+            Token {
+                ty: TokenTypes::LITERAL,
+                modifiers: vec![TokenModifiers::ARGS],
+                val: "\"  a \t b  \"".to_string(),
+                span: Span {
+                    column: 5,
+                    ..Span::start_of("test.dwn")
+                }
             },
             Token {
                 ty: TokenTypes::STRING,
                 modifiers: vec![TokenModifiers::ARGS],
-                val: "Hello World".to_string()
+                val: "c".to_string(),
+                span: Span {
+                    column: 17,
+                    ..Span::start_of("test.dwn")
+                }
             },
         ]
     )
 }
+
+#[test]
+fn tokenizer_reads_escapes() {
+    use crate::runner::run_lines;
+
+    let tokens = tokenize(
+        r#"say "a\n\t\\\"\x41\u{e9}""#.to_string(),
+        &mut run_lines(&[]),
+    );
+
+    assert_eq!(tokens[1].ty, TokenTypes::STRING);
+    assert_eq!(tokens[1].val, "a\n\t\\\"A\u{e9}".to_string());
+}
+
+#[test]
+fn splitting_templates() {
+    use crate::runner::run_lines;
+
+    let tokens = tokenize(
+        r#"say "Hi {name}, {"a}b"}\{""#.to_string(),
+        &mut run_lines(&[]),
+    );
+
+    assert_eq!(tokens[1].ty, TokenTypes::TEMPLATE);
+    assert_eq!(
+        template_parts(&tokens[1]),
+        vec![
+            TemplatePart::Text("Hi ".to_string()),
+            TemplatePart::Placeholder(
+                "name".to_string(),
+                Span {
+                    column: 10,
+                    ..Span::start_of("test.dwn")
+                }
+            ),
+            TemplatePart::Text(", ".to_string()),
+            TemplatePart::Placeholder(
+                "\"a}b\"".to_string(),
+                Span {
+                    column: 18,
+                    ..Span::start_of("test.dwn")
+                }
+            ),
+            TemplatePart::Text("{".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn stripping_comments() {
+    let mut state = LineState::Code;
+
+    assert_eq!(
+        strip_comments("say \"a; b\" ; c", &mut state),
+        "say \"a; b\"".to_string()
+    );
+    assert_eq!(
+        strip_comments("say \"{\"x;\"}\" ;* a", &mut state),
+        "say \"{\"x;\"}\"".to_string()
+    );
+    assert_eq!(state, LineState::BlockComment);
+    assert_eq!(strip_comments("say 1", &mut state), "".to_string());
+    assert_eq!(
+        strip_comments("b *; say 2", &mut state),
+        "     say 2".to_string()
+    );
+    assert_eq!(state, LineState::Code);
+    assert_eq!(
+        strip_comments("say \"a; ", &mut state),
+        "say \"a; ".to_string()
+    );
+    assert_eq!(state, LineState::Str);
+    assert_eq!(strip_comments("b;\" ; c", &mut state), "b;\"".to_string());
+    assert_eq!(state, LineState::Code);
+}
+
+#[test]
+fn tokenizer_respects_precedence() {
+    use crate::runner::run_lines;
+
+    let mut meta = run_lines(&[]);
+
+    let summary = |tokens: Vec<Token>| -> Vec<(TokenTypes, String)> {
+        tokens.into_iter().map(|t| (t.ty, t.val)).collect()
+    };
+
+    assert_eq!(
+        summary(tokenize("1 + 2 * 3".to_string(), &mut meta)),
+        vec![
+            (TokenTypes::FUNC, "sum".to_string()),
+            (TokenTypes::INT, "1".to_string()),
+            (TokenTypes::LITERAL, "2 * 3".to_string()),
+        ]
+    );
+    assert_eq!(
+        summary(tokenize("1 - 2 - 3".to_string(), &mut meta)),
+        vec![
+            (TokenTypes::FUNC, "difference".to_string()),
+            (TokenTypes::LITERAL, "1 - 2".to_string()),
+            (TokenTypes::INT, "3".to_string()),
+        ]
+    );
+    assert_eq!(
+        summary(tokenize("not 1 >= 2 or 5 % 2 == 1".to_string(), &mut meta)),
+        vec![
+            (TokenTypes::FUNC, "logical_or".to_string()),
+            (TokenTypes::LITERAL, "not 1 >= 2".to_string()),
+            (TokenTypes::LITERAL, "5 % 2 == 1".to_string()),
+        ]
+    );
+    assert_eq!(
+        summary(tokenize("say -(1 + 2) > 0 true".to_string(), &mut meta)),
+        vec![
+            (TokenTypes::FUNC, "say".to_string()),
+            (TokenTypes::LITERAL, "-(1 + 2) > 0".to_string()),
+            (TokenTypes::BOOL, "true".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn tokenizer_tracks_block_depth() {
+    use crate::runner::run_lines;
+
+    let mut meta = run_lines(&[]);
+
+    let lines = ["forever {", "\tif true {", "\t\tsay \"}\"", "\t}", "}"];
+    let mut tokens = vec![];
+
+    for (count, line) in lines.iter().enumerate() {
+        meta.span.line = count + 1;
+        tokens = tokenize(line.to_string(), &mut meta);
+    }
+
+    assert_eq!(*meta.scope_depth, 0);
+    assert_eq!(tokens[1].ty, TokenTypes::SCOPE);
+    assert_eq!(
+        tokens[1].val,
+        "\tif true {\n\t\tsay \"}\"\n\t}\n".to_string()
+    );
+    assert_eq!(tokens[1].span.line, 2);
+
+    let tokens = tokenize("if true { say 1 }".to_string(), &mut meta);
+
+    assert_eq!(tokens[2].ty, TokenTypes::SCOPE);
+    assert_eq!(tokens[2].val, " say 1 ".to_string());
+    assert_eq!(tokens[2].span.column, 10);
+}
+
+#[test]
+fn tokenizer_continues_open_statements() {
+    use crate::runner::run_lines;
+
+    let mut meta = run_lines(&[]);
+
+    assert!(tokenize("say (1 +".to_string(), &mut meta).is_empty());
+
+    meta.span.line = 2;
+    let tokens = tokenize("\t2) [3,".to_string(), &mut meta);
+    assert!(tokens.is_empty());
+
+    meta.span.line = 3;
+    let tokens = tokenize("4]".to_string(), &mut meta);
+
+    assert_eq!(tokens[1].val, "1 +\n\t2".to_string());
+    assert_eq!(
+        tokens[1].span,
+        Span {
+            column: 6,
+            ..Span::start_of("test.dwn")
+        }
+    );
+    assert_eq!(tokens[2].ty, TokenTypes::ARRAY);
+    assert_eq!(tokens[2].span.line, 2);
+    assert!(meta.pending.is_empty());
+}
+
+#[test]
+fn tokenizer_reads_else_chains() {
+    use crate::runner::run_lines;
+
+    let mut meta = run_lines(&[]);
+
+    let lines = [
+        "if false {",
+        "\tsay 1",
+        "} else if true { say 2 } else {",
+        "\tsay 3",
+        "}",
+    ];
+    let mut tokens = vec![];
+
+    for (count, line) in lines.iter().enumerate() {
+        meta.span.line = count + 1;
+        tokens = tokenize(line.to_string(), &mut meta);
+    }
+
+    let summary: Vec<(TokenTypes, &str)> = tokens
+        .iter()
+        .map(|t| (t.ty.clone(), t.val.as_str()))
+        .collect();
+
+    assert_eq!(
+        summary,
+        vec![
+            (TokenTypes::FUNC, "if"),
+            (TokenTypes::BOOL, "false"),
+            (TokenTypes::SCOPE, "\tsay 1\n"),
+            (TokenTypes::NAME, "else if"),
+            (TokenTypes::BOOL, "true"),
+            (TokenTypes::SCOPE, " say 2 "),
+            (TokenTypes::NAME, "else"),
+            (TokenTypes::SCOPE, "\tsay 3\n"),
+        ]
+    );
+    assert_eq!(tokens[7].span.line, 4);
+    assert!(tokens[1..]
+        .iter()
+        .all(|t| t.modifiers == vec![TokenModifiers::ARGS]));
+}
+
+#[test]
+fn reporting_unclosed_blocks() {
+    use crate::runner::run_lines;
+
+    let mut meta = run_lines(&[]);
+    let start = Span::start_of("test.dwn");
+
+    // The block opens on line 2 and the file ends on line 4.
+    for (count, line) in ["if true {", "\tsay 1", ""].iter().enumerate() {
+        meta.span.line = count + 2;
+        assert!(tokenize(line.to_string(), &mut meta).is_empty());
+    }
+
+    assert_eq!(
+        unclosed(meta.pending, *meta.scope_depth, meta.scope_token, &start, 4),
+        Some("Error at test.dwn:2:1: Missing closing '}' !".to_string())
+    );
+    assert_eq!(unclosed("", 0, "", &start, 4), None);
+}
+
+#[test]
+fn warning_about_indentation() {
+    use crate::runner::run_lines;
+
+    let mut meta = run_lines(&[]);
+    meta.span.line = 3;
+
+    assert_eq!(
+        indentation_warning(" \tsay 1", &meta),
+        Some(
+            "Warning at test.dwn:3:1: This line is indented with both tabs and spaces!".to_string()
+        )
+    );
+    assert_eq!(indentation_warning("    say 1", &meta), None);
+    assert_eq!(indentation_warning("say 1", &meta), None);
+
+    meta.scope_token.push_str("    say 1\n");
+
+    assert_eq!(
+        indentation_warning("\tsay 2", &meta),
+        Some(
+            "Warning at test.dwn:3:1: This line is indented with tabs but the lines above it use spaces!"
+                .to_string()
+        )
+    );
+    assert_eq!(indentation_warning("    say 2", &meta), None);
+}
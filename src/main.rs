@@ -7,14 +7,19 @@ use clap::{Parser, Subcommand};
 use framework::make_framework;
 use idle::idle;
 use interpreter::interpret_file;
+use std::{process::exit, thread};
 
 mod bytecode;
 mod dwn;
+mod env;
+mod error;
 mod framework;
 mod idle;
 mod interpreter;
 mod lexer;
+mod module;
 mod runner;
+mod value;
 
 /// Dawn (`dwn`) is the interpreter and bytecode compiler for the Dawn Programming Language.
 #[derive(Parser, Debug)]
@@ -40,14 +45,26 @@ enum Commands {
     Framework,
 }
 
+/// The stack of the thread that runs Dawn code. Every function call of Dawn code takes a part
+/// of it, up to the limit on how deep calls can go.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
 fn main() {
     let args = Args::parse();
 
-    match args.command {
-        Commands::Run { file } => interpret_file(file),
-        Commands::Bytec { file, level } => bytecode_compile_file(file, level.unwrap_or(-1)),
-        Commands::Byterun { file } => bytecode_run(file),
-        Commands::Idle => idle(),
-        Commands::Framework => make_framework(),
+    let running = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || match args.command {
+            Commands::Run { file } => interpret_file(file),
+            Commands::Bytec { file, level } => bytecode_compile_file(file, level.unwrap_or(-1)),
+            Commands::Byterun { file } => bytecode_run(file),
+            Commands::Idle => idle(),
+            Commands::Framework => make_framework(),
+        })
+        .expect("Error: Could not start the interpreter!");
+
+    // A panic was already reported by the thread.
+    if running.join().is_err() {
+        exit(101);
     }
 }
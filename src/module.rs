@@ -0,0 +1,208 @@
+//! The modules of Dawn (dwn): other files brought in with `import`.
+
+use std::{
+    collections::HashSet,
+    env, fs,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use crate::{
+    dwn::{CUSTOM_FUNCS, VARIABLES},
+    env::qualify,
+    error::{Error, ErrorKind},
+    interpreter::interpret,
+    lexer::{Token, TokenTypes},
+};
+
+lazy_static! {
+    /// The modules that were imported, by path. They are not run again when imported again.
+    static ref LOADED: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+
+    /// The modules being imported now, each imported by the one before it.
+    static ref LOADING: RwLock<Vec<String>> = RwLock::new(vec![]);
+}
+
+/// What an `import` makes names for.
+pub enum Import {
+    /// The whole module, as `name.thing`. Without a name, the module's file name is used.
+    Module(Option<String>),
+    /// Some functions and variables of the module, under their own names.
+    Names(Vec<String>),
+}
+
+/// Reads the arguments of `import "file.dwn"`, `import "file.dwn" as name` or
+/// `import names... from "file.dwn"` into the token of the file and what to import.
+pub fn read_import(tokens: &[Token]) -> Result<(&Token, Import), Error> {
+    match tokens {
+        [path] => Ok((path, Import::Module(None))),
+        [path, as_, name] if as_.val == "as" => Ok((path, Import::Module(Some(name.val.to_string())))),
+        [names @ .., from, path] if !names.is_empty() && from.val == "from" => {
+            let names = names
+                .iter()
+                .map(|name| match name.ty {
+                    // A function of the importing module is lexed as its key.
+                    TokenTypes::FUNCTION => name.val.rsplit("::").next().unwrap().to_string(),
+                    _ => name.val.to_string(),
+                })
+                .collect();
+
+            Ok((path, Import::Names(names)))
+        }
+        _ => Err(Error::new(
+            ErrorKind::Syntax,
+            "(import) Expected `import \"file.dwn\"`, `import \"file.dwn\" as name` or `import names... from \"file.dwn\"`!",
+        )),
+    }
+}
+
+/// Finds the file `path` of an `import` in the file `from`: next to `from` first, then in each
+/// directory of `DWN_PATH`. Its canonical path is the name of the module.
+pub fn find_module(path: &str, from: &str) -> Result<String, Error> {
+    let mut dirs: Vec<PathBuf> = vec![Path::new(from)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf()];
+
+    if let Some(paths) = env::var_os("DWN_PATH") {
+        dirs.extend(env::split_paths(&paths));
+    }
+
+    for dir in dirs {
+        let file = dir.join(path);
+
+        if file.is_file() {
+            if let Ok(file) = fs::canonicalize(file) {
+                return Ok(file.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    Err(Error::new(
+        ErrorKind::Import,
+        format!("(import) Module `{path}` not found!"),
+    ))
+}
+
+/// The name of the module of the main file `file`: its canonical path, like the modules that
+/// [`find_module`] finds.
+pub fn module_path(file: &str) -> String {
+    match fs::canonicalize(file) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => file.to_string(),
+    }
+}
+
+/// Runs the module at `path`, unless it was already imported. Its top level gets its own global
+/// variables, and the functions it defines are kept under its name. A module that imports one of
+/// the modules importing it is an error.
+pub fn load_module(path: &str) -> Result<(), Error> {
+    if LOADED.read().unwrap().contains(path) {
+        return Ok(());
+    }
+
+    start_loading(path)?;
+
+    let ran = match File::open(path) {
+        Ok(file) => {
+            let outer = VARIABLES.write().unwrap().enter(path.to_string());
+            let ran = interpret(BufReader::new(file), path);
+            VARIABLES.write().unwrap().leave(outer);
+
+            ran
+        }
+        Err(e) => Err(Error::new(
+            ErrorKind::Io,
+            format!("(import) Cannot open `{path}`: {e}"),
+        )),
+    };
+
+    finish_loading(path, ran.is_ok());
+
+    ran
+}
+
+/// Marks the module at `path` (or the main file) as being run, so that importing it again before
+/// it ends is a circular import.
+pub fn start_loading(path: &str) -> Result<(), Error> {
+    let mut loading = LOADING.write().unwrap();
+
+    if let Some(i) = loading.iter().position(|module| module == path) {
+        let cycle: Vec<&str> = loading[i..]
+            .iter()
+            .map(|module| module.as_str())
+            .chain([path])
+            .collect();
+
+        return Err(Error::new(
+            ErrorKind::Import,
+            format!("(import) Circular import: {}", cycle.join(" -> ")),
+        ));
+    }
+
+    loading.push(path.to_string());
+
+    Ok(())
+}
+
+/// Marks the module at `path` as no longer running, and as imported if it ran without an error.
+pub fn finish_loading(path: &str, ran: bool) {
+    let mut loading = LOADING.write().unwrap();
+
+    if let Some(i) = loading.iter().rposition(|module| module == path) {
+        loading.remove(i);
+    }
+
+    if ran {
+        LOADED.write().unwrap().insert(path.to_string());
+    }
+}
+
+/// Makes the names of `import` for the module at `path`, in the module that is running.
+pub fn bind_module(path: &str, import: Import) -> Result<(), Error> {
+    match import {
+        Import::Module(name) => {
+            let name = name.unwrap_or_else(|| {
+                Path::new(path)
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            });
+
+            VARIABLES.write().unwrap().alias(name, path.to_string());
+        }
+        Import::Names(names) => {
+            for name in names {
+                let func = CUSTOM_FUNCS
+                    .read()
+                    .unwrap()
+                    .get(&qualify(path, &name))
+                    .cloned();
+
+                if let Some(func) = func {
+                    let key = VARIABLES.read().unwrap().function_key(&name);
+
+                    CUSTOM_FUNCS.write().unwrap().insert(key, func);
+                    continue;
+                }
+
+                let value = VARIABLES.read().unwrap().global(path, &name).cloned();
+
+                match value {
+                    Some(value) => VARIABLES.write().unwrap().declare(name, value),
+                    None => {
+                        return Err(Error::new(
+                            ErrorKind::Import,
+                            format!("(import) `{name}` is not a function or variable of `{path}`!"),
+                        ))
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
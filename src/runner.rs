@@ -1,7 +1,10 @@
 //! The runner for Dawn (dwn)
 
-use crate::dwn::{run_scope, Metadata, CUSTOM_FUNCS};
+use crate::dwn::{call_custom_func, find_custom_func, get_arg, Builtin, Metadata};
+use crate::error::{Error, ErrorKind};
 use crate::lexer::{tokenize, Token, TokenTypes};
+use crate::value::Value;
+use std::collections::HashMap;
 use std::process::exit;
 use std::sync::RwLockReadGuard;
 
@@ -16,179 +19,661 @@ use std::sync::RwLockReadGuard;
 ///     FUNCTIONS.read().unwrap(),
 ///     VARIABLES.read().unwrap(),
 /// );
-/// assert_eq!(none.val, "None".to_string());
+/// assert_eq!(none, Value::None);
 /// ```
 pub fn run(
     line: String,
-    functions: RwLockReadGuard<
-        '_,
-        std::collections::HashMap<
-            &str,
-            for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        >,
-    >,
+    functions: RwLockReadGuard<'_, HashMap<&str, Builtin>>,
     meta: &mut Metadata,
-) -> Token {
+) -> Value {
     let tokens = tokenize(line, meta);
 
     run_tokens(tokens, functions, meta, false).unwrap()
 }
 
+/// Runs the tokens of a statement. With `capture_errors`, an error is given back; otherwise it is
+/// reported and stops the program.
 pub fn run_tokens(
     tokens: Vec<Token>,
-    functions: RwLockReadGuard<
-        '_,
-        std::collections::HashMap<
-            &str,
-            for<'a> fn(Vec<Token>, &'a mut Metadata) -> Result<Token, String>,
-        >,
-    >,
+    functions: RwLockReadGuard<'_, HashMap<&str, Builtin>>,
     meta: &mut Metadata,
     capture_errors: bool,
-) -> Result<Token, String> {
+) -> Result<Value, Error> {
     let functions_ = functions.clone();
 
-    if tokens.len() > 0 {
-        match tokens[0].ty.clone() {
-            TokenTypes::FUNC => {
-                let fname = tokens[0].val.as_str();
-                let f = functions_.get(fname);
-
-                match f {
-                    Some(f) => {
-                        let mut args: Vec<Token> = vec![];
-                        let mut tokens = tokens.iter();
-
-                        tokens.next();
-
-                        for token in tokens {
-                            args.push(Token { ..token.clone() })
-                        }
-
-                        let ret = if !*meta.in_scope {
-                            f(args, meta)
-                        } else {
-                            return Ok(Token {
-                                ty: TokenTypes::NONE,
-                                modifiers: vec![],
-                                val: "None".to_string(),
-                            });
-                        };
-
-                        match ret {
-                            Ok(token) => return Ok(token),
-                            Err(err) => {
-                                if capture_errors {
-                                    return Err(format!(
-                                        "Error on line {}: {}",
-                                        meta.line_count + 1,
-                                        err
-                                    ));
-                                }
-                                eprintln!("Error on line {}: {}", meta.line_count + 1, err);
-                                exit(1);
-                            }
-                        }
+    if tokens.is_empty() {
+        return Ok(Value::None);
+    }
+
+    let span = tokens[0].span.clone();
+
+    let ret = match tokens[0].ty.clone() {
+        TokenTypes::FUNC => {
+            let fname = tokens[0].val.as_str();
+            let f = functions_.get(fname);
+
+            match f {
+                Some(f) => {
+                    let mut args: Vec<Token> = vec![];
+                    let mut tokens = tokens.iter();
+
+                    tokens.next();
+
+                    for token in tokens {
+                        args.push(Token { ..token.clone() })
                     }
-                    None => {
-                        if capture_errors {
-                            return Err(format!(
-                                "Error: Function {} does not exist!",
-                                tokens[0].val
-                            ));
-                        }
-                        eprintln!("Error: Function {} does not exist!", tokens[0].val);
-                        exit(1);
+
+                    if *meta.scope_depth == 0 {
+                        f(args, meta)
+                    } else {
+                        return Ok(Value::None);
                     }
                 }
+                None => Err(Error::new(
+                    ErrorKind::Name,
+                    format!("Function {} does not exist!", tokens[0].val),
+                )),
             }
-            TokenTypes::NAME => {
-                if capture_errors {
-                    return Err(format!("Error: Name {} not found!", tokens[0].val));
-                }
-                eprintln!("Error: Name {} not found!", tokens[0].val);
-                exit(1);
-            }
-            TokenTypes::CUSTOMFUNC => {
-                let fname = tokens[0].val.as_str();
-                let custom_funcs = match CUSTOM_FUNCS.read() {
-                    Ok(cf) => cf,
-                    Err(e) => {
-                        eprintln!("{e}");
-                        exit(1);
-                    }
-                }
-                .clone();
-
-                let f = custom_funcs.get(fname);
-
-                match f {
-                    Some(f) => {
-                        let mut args: Vec<Token> = vec![];
-                        let mut tokens = tokens.iter();
-
-                        tokens.next();
+        }
+        TokenTypes::NAME => Err(Error::new(
+            ErrorKind::Name,
+            format!("Name {} not found!", tokens[0].val),
+        )),
+        TokenTypes::CUSTOMFUNC => {
+            let fname = tokens[0].val.as_str();
+            let f = find_custom_func(fname);
 
-                        for token in tokens {
-                            args.push(Token { ..token.clone() })
-                        }
+            match f {
+                Some(f) => {
+                    let mut args: Vec<Token> = vec![];
+                    let mut tokens = tokens.iter();
 
-                        let ret = if !*meta.in_scope {
-                            run_scope(f, meta)
-                        } else {
-                            return Ok(Token {
-                                ty: TokenTypes::NONE,
-                                modifiers: vec![],
-                                val: "None".to_string(),
-                            });
-                        };
+                    tokens.next();
 
-                        return Ok(ret);
+                    for token in tokens {
+                        args.push(Token { ..token.clone() })
                     }
-                    None => {
-                        if capture_errors {
-                            return Err(format!(
-                                "Error: Function {} does not exist!",
-                                tokens[0].val
-                            ));
-                        }
-                        eprintln!("Error: Function {} does not exist!", tokens[0].val);
-                        exit(1);
+
+                    if *meta.scope_depth == 0 {
+                        call_custom_func(fname, &f, args, meta)
+                    } else {
+                        return Ok(Value::None);
                     }
                 }
+                None => Err(Error::new(
+                    ErrorKind::Name,
+                    format!("Function {} does not exist!", tokens[0].val),
+                )),
             }
-            ty => {
-                return Ok(Token {
-                    ty,
-                    modifiers: vec![],
-                    val: tokens[0].val.to_string(),
-                })
+        }
+        _ => get_arg(tokens[0].clone(), meta, false),
+    };
+
+    match ret {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            let err = err.at(&span);
+
+            if capture_errors {
+                return Err(err);
             }
+            eprintln!("{err}");
+            exit(1);
         }
-    } else {
-        return Ok(Token {
-            ty: TokenTypes::NONE,
-            modifiers: vec![],
-            val: "None".to_string(),
-        });
     }
 }
 
+/// Runs `lines` like the lines of a file, and gives back the [`Metadata`] they were run with, for
+/// running more.
+#[cfg(test)]
+pub fn run_lines(lines: &[&str]) -> Metadata<'static> {
+    use crate::dwn::get_funcs;
+    use crate::lexer::Span;
+
+    // A shortcut of this helper, not a rule for the interpreter: the state of the lines is
+    // leaked so that it can be given back, and nothing global is cleared. Tests run in parallel
+    // with the same variables, functions and loaded modules, and are only kept apart by using
+    // names and module files that no other test uses.
+    let mut meta = Metadata {
+        span: Span::start_of("test.dwn"),
+        scope: Box::leak(Box::new(0)),
+        scope_depth: Box::leak(Box::new(0)),
+        scope_token: Box::leak(Box::default()),
+        current_tokens: Box::leak(Box::default()),
+        pending: Box::leak(Box::default()),
+        flow: Box::leak(Box::new(None)),
+        loop_depth: Box::leak(Box::new(0)),
+        call_depth: Box::leak(Box::new(0)),
+    };
+
+    for (count, line) in lines.iter().enumerate() {
+        meta.span.line = count + 1;
+        run(line.to_string(), get_funcs(), &mut meta);
+    }
+
+    meta
+}
+
+/// Runs `line` after the lines run with `meta`, giving its value.
+#[cfg(test)]
+fn value_of(line: &str, meta: &mut Metadata) -> Value {
+    use crate::dwn::get_funcs;
+
+    run(line.to_string(), get_funcs(), meta)
+}
+
 #[test]
 fn line_runner() {
+    let mut meta = run_lines(&[]);
+
+    assert_eq!(value_of("say \"Hello World!\"", &mut meta), Value::None);
+}
+
+#[test]
+fn calling_custom_funcs() {
+    let mut meta = run_lines(&["func twice_plus x y { return (x * 2 + y) }"]);
+
+    assert_eq!(value_of("twice_plus 20 2", &mut meta), Value::Int(42));
+}
+
+#[test]
+fn comparing_and_combining() {
+    let mut meta = run_lines(&[]);
+
+    for (line, expected) in [
+        (
+            "[3 >= 3, 2 >= 3, 2 <= 3, 4 <= 3]",
+            "[true, false, true, false]",
+        ),
+        ("[7 % 3, -7 % 3, 7.5 % 2]", "[1, -1, 1.5]"),
+        (
+            "[true and false, false or true, not false]",
+            "[false, true, true]",
+        ),
+        ("not 1 >= 2 and 5 % 2 == 1", "true"),
+        // The second value is not run when the first one decides.
+        (
+            "[false and missing_name, true or missing_name]",
+            "[false, true]",
+        ),
+    ] {
+        assert_eq!(value_of(line, &mut meta).to_string(), expected.to_string());
+    }
+}
+
+#[test]
+fn running_for_loops() {
+    let mut meta = run_lines(&[
+        "let for_total = 0",
+        "for i in range 1 5 { for_total += i }",
+        "for c in \"ab\" { for_total += 10 }",
+        "for x in [100, 200] { if x > 150 { break } else { for_total += x } }",
+    ]);
+
+    assert_eq!(value_of("for_total", &mut meta), Value::Int(130));
+}
+
+#[test]
+fn continuing_loops() {
+    let mut meta = run_lines(&[
+        "let odd_total = 0",
+        "for i in range 10 { if (i % 2) == 0 { continue } else { odd_total += i } }",
+    ]);
+
+    assert_eq!(value_of("odd_total", &mut meta), Value::Int(25));
+}
+
+#[test]
+fn choosing_values_with_if() {
+    let mut meta = run_lines(&[
+        "let chosen = if false { 1 } else { 2 }",
+        "let sign = if chosen < 0 {",
+        "    \"neg\"",
+        "} else if chosen == 0 {",
+        "    \"zero\"",
+        "} else {",
+        "    \"pos\"",
+        "}",
+        "chosen = if false { 3 }",
+    ]);
+
+    assert_eq!(value_of("sign", &mut meta), Value::Str("pos".to_string()));
+    assert_eq!(value_of("chosen", &mut meta), Value::None);
+}
+
+#[test]
+fn running_indented_blocks() {
+    let mut meta = run_lines(&[
+        "let indents = []",
+        "if true {",
+        "    push indents 1",
+        "    if true {",
+        "        push indents 2",
+        "    }",
+        "}",
+        "for indent in [3, 4] {",
+        "push indents indent",
+        "}",
+    ]);
+
+    assert_eq!(
+        value_of("indents", &mut meta).to_string(),
+        "[1, 2, 3, 4]".to_string()
+    );
+}
+
+#[test]
+fn passing_closures() {
+    let mut meta = run_lines(&[
+        "func make_multiplier n { return fn x { return x * n } }",
+        "func apply_to_ten f { return (f 10) }",
+        "let triple = (make_multiplier 3)",
+    ]);
+
+    assert_eq!(value_of("apply_to_ten triple", &mut meta), Value::Int(30));
+}
+
+#[test]
+fn sharing_closure_variables() {
+    let mut meta = run_lines(&[
+        "func make_counter {",
+        "    let count = 0",
+        "    return fn {",
+        "        count += 1",
+        "        return count",
+        "    }",
+        "}",
+        "let counter = (make_counter)",
+        "let other_counter = (make_counter)",
+        "let counts = [(counter), (counter), (other_counter), (counter)]",
+        "func count_twice {",
+        "    let total = 0",
+        "    let bump = fn { total += 1 }",
+        "    bump",
+        "    bump",
+        "    return total",
+        "}",
+    ]);
+
+    assert_eq!(value_of("counts", &mut meta).to_string(), "[1, 2, 1, 3]");
+    assert_eq!(value_of("count_twice", &mut meta), Value::Int(2));
+    assert_eq!(value_of("[counter]", &mut meta).to_string(), "[<fn>]");
+}
+
+#[test]
+fn using_maps() {
+    let mut meta = run_lines(&[
+        "let ages = #{\"ann\": 30, \"bo\": 5 * 5}",
+        "set ages \"cy\" 40",
+        "remove ages \"ann\"",
+        "let age_total = 0",
+        "for name age in ages { age_total += age }",
+    ]);
+
+    assert_eq!(
+        value_of("ages", &mut meta).to_string(),
+        "{\"bo\": 25, \"cy\": 40}".to_string()
+    );
+    assert_eq!(
+        value_of("age_total + ages[\"bo\"]", &mut meta),
+        Value::Int(90)
+    );
+}
+
+#[test]
+fn changing_arrays() {
+    let mut meta = run_lines(&[
+        "let grid = [[1, 2], [\"a, b\", 4]]",
+        "let row = 1",
+        "grid[0] = grid[row][0]",
+        "push grid [5]",
+        "insert grid 0 (sort [3, 1, 2])",
+    ]);
+
+    assert_eq!(
+        value_of("grid", &mut meta).to_string(),
+        "[[1, 2, 3], \"a, b\", [\"a, b\", 4], [5]]".to_string()
+    );
+    assert_eq!(
+        value_of("pop grid", &mut meta),
+        Value::Array(vec![Value::Int(5)])
+    );
+}
+
+#[test]
+fn changing_arrays_with_their_own_items() {
+    let mut meta = run_lines(&[
+        "let moved = [1, 2]",
+        "push moved (pop moved)",
+        "let rotated = [1, 2]",
+        "insert rotated 0 (pop rotated)",
+        "let shrunk = [1, 2, 3]",
+        "remove shrunk (len shrunk) - 1",
+    ]);
+
+    assert_eq!(value_of("moved", &mut meta).to_string(), "[1, 2]");
+    assert_eq!(value_of("rotated", &mut meta).to_string(), "[2, 1]");
+    assert_eq!(value_of("shrunk", &mut meta).to_string(), "[1, 2]");
+}
+
+#[test]
+fn slicing() {
+    let mut meta = run_lines(&["let digits = [0, 1, 2, 3, 4, 5]", "let word = \"dawn\""]);
+
+    assert_eq!(
+        value_of("digits[1:-1:2]", &mut meta),
+        Value::Array(vec![Value::Int(1), Value::Int(3)])
+    );
+    assert_eq!(
+        value_of("word[::-1]", &mut meta),
+        Value::Str("nwad".to_string())
+    );
+    assert_eq!(
+        value_of("digits[(2 * 2):]", &mut meta).to_string(),
+        "[4, 5]".to_string()
+    );
+}
+
+#[test]
+fn doing_exact_arithmetic() {
+    let mut meta = run_lines(&[]);
+
+    assert_eq!(
+        value_of("9007199254740993 + 2", &mut meta),
+        Value::Int(9007199254740995)
+    );
+    assert_eq!(
+        value_of("[7 / 2, 6 / 2, 7 // 2, -7 // 2, 2.5 * 2]", &mut meta).to_string(),
+        "[3.5, 3.0, 3, -3, 5.0]".to_string()
+    );
+    assert_eq!(
+        value_of("9223372036854775807 + 1", &mut meta).to_string(),
+        "9223372036854775808".to_string()
+    );
+}
+
+#[test]
+fn doing_decimal_arithmetic() {
     use crate::dwn::get_funcs;
 
-    let none = run(
-        "say \"Hello World!\"".to_string(),
+    let mut meta = run_lines(&[]);
+
+    let mixed = run_tokens(
+        tokenize("0.1d + 0.5".to_string(), &mut meta),
         get_funcs(),
-        &mut Metadata {
-            line_count: 0,
-            scope: &mut 0,
-            in_scope: &mut false,
-            scope_token: &mut String::new(),
-            current_tokens: &mut vec![],
-        },
+        &mut meta,
+        true,
+    );
+
+    assert_eq!(
+        value_of("0.1d + 0.2d", &mut meta).to_string(),
+        "0.3".to_string()
+    );
+    assert_eq!(
+        value_of("0.1d + 0.2d == 0.3d", &mut meta),
+        Value::Bool(true)
+    );
+    assert!(mixed.is_err_and(|err| err.message.contains("Cannot mix a decimal with a float")));
+}
+
+#[test]
+fn catching_errors() {
+    use crate::dwn::get_funcs;
+
+    let mut meta = run_lines(&[
+        "let caught = []",
+        "try { int \"abc\" } catch err { push caught err[\"kind\"] }",
+        "try { say caught[3] } catch err { push caught err[\"kind\"] } finally { push caught 0 }",
+        "try { raise \"Custom\" \"no\" } catch err { push caught err[\"message\"] }",
+        "try { raise \"Custom\" \"no\" \"extra\" } catch err { push caught err[\"message\"] }",
+    ]);
+
+    let uncaught = run_tokens(
+        tokenize("raise \"Custom\" \"no\"".to_string(), &mut meta),
+        get_funcs(),
+        &mut meta,
+        true,
+    );
+
+    assert_eq!(
+        value_of("caught", &mut meta).to_string(),
+        "[\"ValueError\", \"IndexError\", 0, \"no\", \"(raise) Too many arguments!\"]".to_string()
+    );
+    assert!(uncaught.is_err_and(|err| err.to_string() == "Error at test.dwn:5:1: no"));
+}
+
+#[test]
+fn catching_operand_types() {
+    let mut meta = run_lines(&[
+        "let operand_kinds = []",
+        "try { say \"x\" * 2 } catch err { push operand_kinds err[\"kind\"] }",
+        "try { say true + 1 } catch err { push operand_kinds err[\"kind\"] }",
+    ]);
+
+    assert_eq!(
+        value_of("operand_kinds", &mut meta).to_string(),
+        "[\"TypeError\", \"TypeError\"]"
+    );
+}
+
+#[test]
+fn limiting_call_depth() {
+    // Calls at the limit need more stack than a test thread has, like the interpreter's thread.
+    let running = std::thread::Builder::new()
+        .stack_size(256 * 1024 * 1024)
+        .spawn(|| {
+            let mut meta = run_lines(&[
+                "let depth_kinds = []",
+                "func plunge n { return (plunge (n + 1)) }",
+                "try { plunge 0 } catch err { push depth_kinds err[\"kind\"] }",
+                "func countdown n { if n == 0 { return 0 } else { return (countdown (n - 1)) } }",
+            ]);
+
+            assert_eq!(
+                value_of("depth_kinds", &mut meta).to_string(),
+                "[\"RecursionError\"]"
+            );
+            assert_eq!(value_of("countdown 900", &mut meta), Value::Int(0));
+        })
+        .unwrap();
+
+    running.join().unwrap();
+}
+
+#[test]
+fn returning_outside_functions() {
+    let mut meta = run_lines(&[
+        "let return_kinds = []",
+        "try { if true { return 1 } } catch err { push return_kinds err[\"kind\"] }",
+    ]);
+
+    assert_eq!(
+        value_of("return_kinds", &mut meta).to_string(),
+        "[\"SyntaxError\"]"
+    );
+}
+
+#[test]
+fn shadowing_variables() {
+    let mut meta = run_lines(&[
+        "let shade = 1",
+        "let shades = []",
+        "if true { let shade = 2 }",
+        "push shades shade",
+        "if true { shade = 3 }",
+        "push shades shade",
+        "for shade in [4] { push shades shade }",
+        "push shades shade",
+    ]);
+
+    assert_eq!(
+        value_of("shades", &mut meta).to_string(),
+        "[1, 3, 4, 3]".to_string()
+    );
+}
+
+/// Writes the module `name` for a test, in a directory of this run of the tests, and gives its
+/// canonical path.
+#[cfg(test)]
+fn write_module(name: &str, code: &str) -> String {
+    use std::{env, fs};
+
+    let dir = env::temp_dir().join(format!("dwn-tests-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join(name);
+    fs::write(&path, code).unwrap();
+
+    // Modules are named by their canonical path.
+    fs::canonicalize(path)
+        .unwrap()
+        .to_string_lossy()
+        .to_string()
+}
+
+#[test]
+fn importing_modules() {
+    let path = write_module(
+        "importing_modules.dwn",
+        "let base = 10\nfunc add_base x {\n    return (x + base)\n}\n",
+    );
+
+    let mut meta = run_lines(&[
+        &format!("import \"{path}\" as tm"),
+        &format!("import add_base from \"{path}\""),
+    ]);
+
+    assert_eq!(
+        value_of("[tm.base, (tm.add_base 1), (add_base 2)]", &mut meta).to_string(),
+        "[10, 11, 12]".to_string()
+    );
+    assert_eq!(
+        value_of("[tm.add_base, add_base]", &mut meta).to_string(),
+        "[add_base, add_base]".to_string()
+    );
+}
+
+#[test]
+fn string_functions() {
+    let mut meta = run_lines(&[
+        "let text = \"  Hello, Dawn  \"",
+        "let trimmed = (trim text)",
+        "let greeting = \"Hi\"",
+        "greeting += \"!\"",
+        "let repeat_errors = []",
+        "try { repeat \"ab\" 99999999999999999999 } catch err { push repeat_errors err[\"kind\"] }",
+        "try { repeat \"ab\" 9223372036854775807 } catch err { push repeat_errors err[\"message\"] }",
+        "try { repeat \"a\" 99999999999999 } catch err { push repeat_errors err[\"kind\"] }",
+    ]);
+
+    for (line, expected) in [
+        ("(upper trimmed) + (lower trimmed)", "HELLO, DAWNhello, dawn"),
+        ("split trimmed \", \"", "[\"Hello\", \"Dawn\"]"),
+        ("join (split \"a b  c\") \"-\"", "a-b-c"),
+        ("replace trimmed \"Dawn\" \"World\"", "Hello, World"),
+        ("[(contains trimmed \"Dawn\"), (starts_with trimmed \"He\"), (ends_with trimmed \"x\")]", "[true, true, false]"),
+        ("[(find \"héllo\" \"llo\"), (find trimmed \"x\"), (len trimmed)]", "[2, -1, 11]"),
+        ("repeat \"ab\" 3", "ababab"),
+        ("chars \"héy\"", "[\"h\", \"é\", \"y\"]"),
+        ("greeting + \" \" + trimmed", "Hi! Hello, Dawn"),
+        ("repeat_errors", "[\"ValueError\", \"(repeat) Cannot repeat a string 9223372036854775807 times, the count is too large!\", \"ValueError\"]"),
+        ("repeat \"\" 9223372036854775807", ""),
+    ] {
+        assert_eq!(value_of(line, &mut meta).to_string(), expected.to_string());
+    }
+}
+
+#[test]
+fn importing_itself() {
+    use crate::interpreter::run_file;
+
+    let path = write_module("importing_itself.dwn", "import \"importing_itself.dwn\"\n");
+
+    let ran = run_file(&path);
+
+    assert!(
+        ran.is_err_and(|err| err.message == format!("(import) Circular import: {path} -> {path}"))
+    );
+}
+
+#[test]
+fn importing_in_a_circle() {
+    use crate::interpreter::run_file;
+
+    let first = write_module(
+        "importing_in_a_circle_1.dwn",
+        "let circle_runs = []\npush circle_runs 1\nimport \"importing_in_a_circle_2.dwn\"\n",
     );
+    let second = write_module(
+        "importing_in_a_circle_2.dwn",
+        "import \"importing_in_a_circle_1.dwn\"\n",
+    );
+
+    let ran = run_file(&first);
+    let mut meta = run_lines(&[]);
+
+    assert!(ran
+        .is_err_and(|err| err.message
+            == format!("(import) Circular import: {first} -> {second} -> {first}")));
+    assert_eq!(
+        value_of("circle_runs", &mut meta).to_string(),
+        "[1]".to_string()
+    );
+}
+
+#[test]
+fn looping_over_long_ranges() {
+    let mut meta = run_lines(&[
+        "let near_max = []",
+        "for i in range 9223372036854775800 9223372036854775807 5 { push near_max i }",
+        "let first_of_many = 0",
+        "for i in range 1 1000000000 { if i > 2 { break } else { first_of_many = i } }",
+    ]);
+
+    assert_eq!(
+        value_of("near_max", &mut meta).to_string(),
+        "[9223372036854775800, 9223372036854775805]".to_string()
+    );
+    assert_eq!(value_of("first_of_many", &mut meta), Value::Int(2));
+}
 
-    assert_eq!(none.val, "None".to_string());
+#[test]
+fn reporting_item_lines() {
+    use crate::dwn::get_funcs;
+
+    let mut meta = run_lines(&[]);
+
+    for (count, line) in ["let item_lines = [1,", " 2,"].iter().enumerate() {
+        meta.span.line = count + 1;
+        assert!(tokenize(line.to_string(), &mut meta).is_empty());
+    }
+
+    meta.span.line = 3;
+    let tokens = tokenize(" missing_item]".to_string(), &mut meta);
+    let ran = run_tokens(tokens, get_funcs(), &mut meta, true);
+
+    assert!(ran.is_err_and(
+        |err| err.to_string() == "Error at test.dwn:3:2: Name 'missing_item' does not exist!"
+    ));
+}
+
+#[test]
+fn setting_nested_items() {
+    let mut meta = run_lines(&[
+        "let nested_grid = [[1, 2], [3, 4]]",
+        "nested_grid[0][1] = 9",
+        "let nested_map = #{\"rows\": [[1, 2]], \"meta\": #{\"n\": 0}}",
+        "nested_map[\"rows\"][0][1] = 9",
+        "nested_map[\"meta\"][\"n\"] = 5",
+        "nested_map[\"meta\"][\"new\"] = true",
+    ]);
+
+    assert_eq!(
+        value_of("nested_grid", &mut meta).to_string(),
+        "[[1, 9], [3, 4]]".to_string()
+    );
+    assert_eq!(
+        value_of("nested_map", &mut meta).to_string(),
+        "{\"rows\": [[1, 9]], \"meta\": {\"n\": 5, \"new\": true}}".to_string()
+    );
 }
@@ -0,0 +1,162 @@
+//! The values that Dawn (dwn) programs work with, as opposed to the tokens they are written in.
+
+use std::{
+    fmt::{self, Display},
+    sync::Arc,
+};
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use num_bigint::BigInt;
+
+use crate::{dwn::CustomFunc, lexer::TokenTypes};
+
+/// A value made by running Dawn code: what variables hold, what arguments are turned into and what
+/// functions give back.
+///
+/// Examples:
+///
+/// ```rust
+/// let value = Value::Array(vec![Value::Int(1), Value::Str("a".to_string())]);
+///
+/// assert_eq!(value.to_string(), "[1, \"a\"]".to_string());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    None,
+    Bool(bool),
+    Int(i64),
+    /// An integer that does not fit in an `Int`. Integers that do fit are always an `Int`.
+    BigInt(BigInt),
+    Float(f64),
+    /// An exact decimal number, written like `19.99d`.
+    Decimal(BigDecimal),
+    Str(String),
+    Array(Vec<Value>),
+    /// The keys and values of a map, in the order they were added.
+    Map(Vec<(Value, Value)>),
+    /// A function used as a value.
+    Function(Function),
+}
+
+/// A function held by a [`Value`].
+#[derive(Clone, Debug)]
+pub enum Function {
+    /// A function defined with `func`. Holds its key in `CUSTOM_FUNCS`.
+    Named(String),
+    /// A function made with `fn`. It lives as long as the values holding it.
+    Anonymous(Arc<CustomFunc>),
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Function::Named(a), Function::Named(b)) => a == b,
+            (Function::Anonymous(a), Function::Anonymous(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    /// The type of the value, as named in error messages.
+    pub fn ty(&self) -> TokenTypes {
+        match self {
+            Value::None => TokenTypes::NONE,
+            Value::Bool(_) => TokenTypes::BOOL,
+            Value::Int(_) | Value::BigInt(_) => TokenTypes::INT,
+            Value::Float(_) => TokenTypes::FLOAT,
+            Value::Decimal(_) => TokenTypes::DECIMAL,
+            Value::Str(_) => TokenTypes::STRING,
+            Value::Array(_) => TokenTypes::ARRAY,
+            Value::Map(_) => TokenTypes::MAP,
+            Value::Function(_) => TokenTypes::FUNCTION,
+        }
+    }
+
+    /// The function the value is, if it is one.
+    pub fn function(&self) -> Option<Function> {
+        match self {
+            Value::Function(function) => Some(function.clone()),
+            _ => None,
+        }
+    }
+
+    /// Makes an integer value, which is an `Int` if it fits in one.
+    pub fn integer(int: BigInt) -> Value {
+        match int.to_i64() {
+            Some(int) => Value::Int(int),
+            None => Value::BigInt(int),
+        }
+    }
+
+    /// Whether the value is an integer, a float or a decimal.
+    pub fn is_number(&self) -> bool {
+        matches!(
+            self,
+            Value::Int(_) | Value::BigInt(_) | Value::Float(_) | Value::Decimal(_)
+        )
+    }
+
+    /// Shows the value as it is written in code, so strings are in quotes. Used for the items of
+    /// arrays and maps.
+    pub fn repr(&self) -> String {
+        match self {
+            Value::Str(string) => quote(string),
+            value => value.to_string(),
+        }
+    }
+}
+
+/// Shows a value the way `say` prints it. Strings inside arrays and maps are shown in quotes.
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::None => write!(f, "None"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+            Value::Int(int) => write!(f, "{int}"),
+            Value::BigInt(int) => write!(f, "{int}"),
+            Value::Float(float) if float.fract() == 0.0 => write!(f, "{float:.1}"),
+            Value::Float(float) => write!(f, "{float}"),
+            Value::Decimal(decimal) => write!(f, "{}", decimal.to_plain_string()),
+            Value::Str(string) => write!(f, "{string}"),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(Value::repr).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Map(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key.repr(), value.repr()))
+                    .collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            // The key of a module's function starts with the module's path, which is left out.
+            Value::Function(Function::Named(key)) => {
+                write!(f, "{}", key.rsplit("::").next().unwrap_or(key))
+            }
+            Value::Function(Function::Anonymous(_)) => write!(f, "<fn>"),
+        }
+    }
+}
+
+/// Puts a string in quotes, escaping what would not be read back as written.
+fn quote(string: &str) -> String {
+    let mut quoted = String::from('"');
+
+    for c in string.chars() {
+        match c {
+            '\\' | '"' | '{' | '}' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}